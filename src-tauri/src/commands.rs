@@ -1,10 +1,21 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-use rmcp::model::Tool;
+use rmcp::model::{
+    LoggingLevel, Prompt, ReadResourceResult, Resource, ResourceTemplate, Root, Tool,
+};
 use tauri::AppHandle;
 
 use crate::daemon;
 use crate::mcp;
+use crate::mcp::audit::{Entry, Filter};
+use crate::mcp::config::McpServerConfig;
+use crate::mcp::health::RunningServer;
+use crate::mcp::metrics::ToolMetrics;
+use crate::mcp::content::{PromptResult, ToolResult};
+use crate::mcp::policy::PolicyRule;
+use crate::mcp::sampling::SamplingResponse;
+use crate::mcp::FromServer;
 use crate::State;
 
 macro_rules! ok_or_err {
@@ -21,24 +32,31 @@ macro_rules! ok_or_err {
 
 #[tauri::command]
 pub async fn get_metadata(
-    command: String,
-    args: Vec<String>,
-    env: HashMap<String, String>,
+    config: McpServerConfig,
+    state: tauri::State<'_, State>,
     app: AppHandle,
 ) -> Result<String, String> {
-    ok_or_err!(mcp::peer_info(command, args, env, app).await)
+    ok_or_err!(mcp::peer_info(config, state, app).await)
+}
+
+#[tauri::command]
+pub fn invalidate_metadata(config: Option<McpServerConfig>, state: tauri::State<'_, State>) {
+    mcp::invalidate_peer_info(config, state)
+}
+
+#[tauri::command]
+pub fn validate_mcp_command(command: String, cwd: Option<String>) -> Result<(), String> {
+    ok_or_err!(mcp::validate(&command, cwd.as_deref()))
 }
 
 #[tauri::command]
 pub async fn start_mcp_server(
     session_id: i32,
-    command: String,
-    args: Vec<String>,
-    env: HashMap<String, String>,
+    config: McpServerConfig,
     app: AppHandle,
 ) -> Result<(), String> {
-    log::debug!("-> start_mcp_server({}, {})", session_id, command);
-    ok_or_err!(mcp::start(session_id, command, args, env, app).await)
+    log::debug!("-> start_mcp_server({}, {:?})", session_id, config.transport);
+    ok_or_err!(mcp::start(session_id, config, app).await)
 }
 
 #[tauri::command]
@@ -64,9 +82,186 @@ pub async fn call_mcp_tool(
     session_id: i32,
     name: String,
     arguments: serde_json::Map<String, serde_json::Value>,
+    request_id: Option<String>,
+    timeout_ms: Option<u64>,
     state: tauri::State<'_, State>,
-) -> Result<String, String> {
-    ok_or_err!(mcp::call_tool(session_id, name, arguments, state).await)
+    app: AppHandle,
+) -> Result<ToolResult, String> {
+    ok_or_err!(
+        mcp::call_tool(session_id, name, arguments, request_id, timeout_ms, state, app).await
+    )
+}
+
+#[tauri::command]
+pub async fn cancel_mcp_tool_call(
+    request_id: String,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    log::debug!("-> cancel_mcp_tool_call({})", request_id);
+    ok_or_err!(mcp::cancel_tool_call(request_id, state).await)
+}
+
+#[tauri::command]
+pub async fn respond_mcp_sampling(
+    id: String,
+    response: SamplingResponse,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    log::debug!("-> respond_mcp_sampling({})", id);
+    ok_or_err!(mcp::respond_to_sampling(id, response, state).await)
+}
+
+#[tauri::command]
+pub async fn set_mcp_policy(
+    rules: Vec<PolicyRule>,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    ok_or_err!(mcp::set_policy(rules, state).await)
+}
+
+#[tauri::command]
+pub async fn respond_mcp_tool_approval(
+    id: String,
+    approved: bool,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    log::debug!("-> respond_mcp_tool_approval({}, {})", id, approved);
+    ok_or_err!(mcp::respond_to_tool_approval(id, approved, state).await)
+}
+
+#[tauri::command]
+pub async fn query_mcp_tool_calls(
+    filter: Filter,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<Entry>, String> {
+    ok_or_err!(mcp::tool_calls(filter, state).await)
+}
+
+#[tauri::command]
+pub async fn export_mcp_tool_calls(
+    filter: Filter,
+    path: PathBuf,
+    state: tauri::State<'_, State>,
+) -> Result<usize, String> {
+    ok_or_err!(mcp::export_tool_calls(filter, path, state).await)
+}
+
+#[tauri::command]
+pub async fn get_mcp_metrics(state: tauri::State<'_, State>) -> Result<Vec<ToolMetrics>, String> {
+    Ok(state.metrics.snapshot())
+}
+
+#[tauri::command]
+pub async fn serve_mcp_metrics(
+    port: Option<u16>,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    ok_or_err!(mcp::serve_metrics(port, state).await)
+}
+
+#[tauri::command]
+pub async fn list_running_servers(
+    state: tauri::State<'_, State>,
+) -> Result<Vec<RunningServer>, String> {
+    Ok(mcp::running_servers(state).await)
+}
+
+#[tauri::command]
+pub async fn set_mcp_roots(
+    session_id: i32,
+    roots: Vec<Root>,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    ok_or_err!(mcp::set_roots(session_id, roots, state).await)
+}
+
+#[tauri::command]
+pub async fn list_mcp_resources(
+    session_id: i32,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<FromServer<Resource>>, String> {
+    ok_or_err!(mcp::list_resources(session_id, state).await)
+}
+
+#[tauri::command]
+pub async fn list_mcp_resource_templates(
+    session_id: i32,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<FromServer<ResourceTemplate>>, String> {
+    ok_or_err!(mcp::list_resource_templates(session_id, state).await)
+}
+
+#[tauri::command]
+pub async fn read_mcp_resource(
+    session_id: i32,
+    server: String,
+    uri: String,
+    state: tauri::State<'_, State>,
+) -> Result<ReadResourceResult, String> {
+    ok_or_err!(mcp::read_resource(session_id, server, uri, state).await)
+}
+
+#[tauri::command]
+pub async fn subscribe_mcp_resource(
+    session_id: i32,
+    server: String,
+    uri: String,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    log::debug!("-> subscribe_mcp_resource({}, {}, {})", session_id, server, uri);
+    ok_or_err!(mcp::subscribe_resource(session_id, server, uri, state).await)
+}
+
+#[tauri::command]
+pub async fn unsubscribe_mcp_resource(
+    session_id: i32,
+    server: String,
+    uri: String,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    log::debug!("-> unsubscribe_mcp_resource({}, {}, {})", session_id, server, uri);
+    ok_or_err!(mcp::unsubscribe_resource(session_id, server, uri, state).await)
+}
+
+#[tauri::command]
+pub async fn list_mcp_prompts(
+    session_id: i32,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<FromServer<Prompt>>, String> {
+    log::debug!("-> list_mcp_prompts({})", session_id);
+    ok_or_err!(mcp::list_prompts(session_id, state).await)
+}
+
+#[tauri::command]
+pub async fn get_mcp_prompt(
+    session_id: i32,
+    server: String,
+    name: String,
+    arguments: HashMap<String, String>,
+    state: tauri::State<'_, State>,
+) -> Result<PromptResult, String> {
+    log::debug!("-> get_mcp_prompt({}, {}, {})", session_id, server, name);
+    ok_or_err!(mcp::get_prompt(session_id, server, name, arguments, state).await)
+}
+
+#[tauri::command]
+pub async fn set_mcp_log_level(
+    session_id: i32,
+    server: String,
+    level: LoggingLevel,
+    state: tauri::State<'_, State>,
+) -> Result<(), String> {
+    log::debug!("-> set_mcp_log_level({}, {}, {:?})", session_id, server, level);
+    ok_or_err!(mcp::set_log_level(session_id, server, level, state).await)
+}
+
+#[tauri::command]
+pub async fn get_mcp_server_logs(
+    session_id: i32,
+    name: String,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<String>, String> {
+    ok_or_err!(mcp::logs(session_id, name, state).await)
 }
 
 #[tauri::command]
@@ -13,11 +13,12 @@ mod window;
 use std::sync::OnceLock;
 
 use anyhow::Result;
-use process::Process;
 use tauri::{AppHandle, Manager, RunEvent};
 use tauri_plugin_deep_link::DeepLinkExt;
 use tauri_plugin_window_state::{AppHandleExt, StateFlags, WindowExt};
 
+use crate::mcp::audit::Audit;
+use crate::mcp::orphans::Groups;
 use crate::migrations::migrations;
 use crate::state::State;
 use crate::window::configure_window;
@@ -36,9 +37,27 @@ fn setup_app(app: &mut tauri::App) -> Result<()> {
 
     log_panics::init();
 
+    // Where tauri-plugin-sql keeps `sqlite:tome.db`.
+    let database = app.path().app_config_dir()?.join("tome.db");
+
+    // Servers left running by a previous run that crashed, before we start
+    // any of our own.
+    let groups = app.path().app_local_data_dir()?.join("mcp-groups.json");
+    mcp::orphans::reap(&groups);
+
     app.manage(State {
         sessions: Default::default(),
         watchers: Default::default(),
+        tool_calls: Default::default(),
+        sampling: Default::default(),
+        policy: Default::default(),
+        tool_approvals: Default::default(),
+        audit: Audit::new(database),
+        metrics: Default::default(),
+        metrics_endpoint: Default::default(),
+        metadata: Default::default(),
+        pool: Default::default(),
+        groups: Groups::new(groups),
     });
 
     if let Err(e) = configure_window(&window) {
@@ -99,11 +118,32 @@ fn main() {
             crate::http::fetch,
             // MCP
             commands::get_metadata,
+            commands::invalidate_metadata,
             commands::get_mcp_tools,
             commands::call_mcp_tool,
+            commands::cancel_mcp_tool_call,
+            commands::respond_mcp_sampling,
+            commands::set_mcp_policy,
+            commands::respond_mcp_tool_approval,
+            commands::query_mcp_tool_calls,
+            commands::export_mcp_tool_calls,
+            commands::get_mcp_metrics,
+            commands::serve_mcp_metrics,
+            commands::list_running_servers,
+            commands::set_mcp_roots,
+            commands::validate_mcp_command,
             commands::start_mcp_server,
             commands::stop_mcp_server,
             commands::rename_mcp_server,
+            commands::get_mcp_server_logs,
+            commands::set_mcp_log_level,
+            commands::list_mcp_resources,
+            commands::list_mcp_resource_templates,
+            commands::read_mcp_resource,
+            commands::subscribe_mcp_resource,
+            commands::unsubscribe_mcp_resource,
+            commands::list_mcp_prompts,
+            commands::get_mcp_prompt,
             // Sessions
             commands::stop_session,
             // Misc
@@ -117,16 +157,11 @@ fn main() {
         Ok(app) => {
             app.run(|app, event| {
                 if let RunEvent::Exit = event {
-                    match Process::current() {
-                        Ok(p) => {
-                            if let Err(e) = p.kill_tree() {
-                                log::error!("Failed to kill child processes on exit: {}", e);
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Failed to get current process, cannot kill children: {}", e);
-                        }
-                    }
+                    // Give servers a chance to exit cleanly, then make sure
+                    // nothing we started outlives us.
+                    tauri::async_runtime::block_on(mcp::shutdown(app));
+                    app.state::<State>().groups.kill_all();
+                    mcp::limits::restore();
                 } else if let RunEvent::ExitRequested { .. } = event {
                      let _ = app.save_window_state(StateFlags::all());
                 }
@@ -1,34 +1,132 @@
+pub(crate) mod annotations;
+pub(crate) mod audit;
+pub(crate) mod client;
+pub(crate) mod config;
+pub(crate) mod content;
+pub(crate) mod health;
+pub(crate) mod limits;
+pub(crate) mod logs;
+pub(crate) mod metadata;
+pub(crate) mod metrics;
+pub(crate) mod orphans;
+pub(crate) mod policy;
+pub(crate) mod pool;
 pub(crate) mod process;
+pub(crate) mod sampling;
+pub(crate) mod sandbox;
 pub(crate) mod server;
+pub(crate) mod supervisor;
+pub(crate) mod transport;
 
 use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::state::State;
+use crate::state::{qualify, Route, State};
 
 use anyhow::{anyhow, Result};
-use rmcp::model::CallToolRequestParam;
-use rmcp::model::Tool;
-use server::McpServer;
-use tauri::{AppHandle, Manager};
+use audit::Filter;
+use chrono::Utc;
+use config::McpServerConfig;
+use content::{PromptResult, ToolResult};
+use health::{RunningServer, Status};
+use metadata::Metadata;
+use policy::{Action, Policy, PolicyError, PolicyRule, ToolApproval, ToolApprovalCancelled};
+use sampling::SamplingResponse;
+use rmcp::model::{CallToolRequestParam, Root};
+use rmcp::model::{LoggingLevel, Prompt, ReadResourceResult, Resource, ResourceTemplate, Tool};
+use serde::Serialize;
+use server::{CallError, McpServer};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+/// Launchers we ship our own copies of, as bundled resources.
+const BUNDLED: [&str; 5] = ["python", "uvx", "node", "npx", "bunx"];
 
 // This function is now Linux-only, so no platform-specific logic is needed.
+//
+// Known launchers run from our bundled resources. Anything else has to be an
+// absolute path, or an executable on `PATH`.
 pub fn get_os_specific_command(command: &str, app: &AppHandle) -> Result<Command> {
-    let os_specific_command = match command {
-        "python" => "python",
-        "uvx" => "uvx",
-        "node" => "node",
-        "npx" => "npx",
-        "bunx" => "bunx",
-        _ => return Err(anyhow!("{} servers not supported.", command)),
-    };
+    if !BUNDLED.contains(&command) {
+        return resolve_executable(command).map(Command::new);
+    }
 
     app.path()
-        .resolve(os_specific_command, tauri::path::BaseDirectory::Resource)
+        .resolve(command, tauri::path::BaseDirectory::Resource)
         .map(Command::new)
         .map_err(anyhow::Error::from)
 }
 
+pub fn resolve_executable(command: &str) -> Result<PathBuf> {
+    let path = Path::new(command);
+
+    if path.is_absolute() {
+        return if is_executable(path) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(anyhow!("{} does not exist or is not executable.", command))
+        };
+    }
+
+    if command.contains('/') {
+        return Err(anyhow!("{} must be an absolute path, or a command on your PATH.", command));
+    }
+
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(command))
+        .find(|path| is_executable(path))
+        .ok_or_else(|| anyhow!("{} not found on your PATH.", command))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Check a local server's command and working directory, before it's saved.
+pub fn validate(command: &str, cwd: Option<&str>) -> Result<()> {
+    if !BUNDLED.contains(&command) {
+        resolve_executable(command)?;
+    }
+
+    if let Some(cwd) = cwd {
+        if !Path::new(cwd).is_dir() {
+            return Err(anyhow!("Working directory {} does not exist.", cwd));
+        }
+    }
+
+    Ok(())
+}
+
+/// Something a server published, tagged with the server it came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct FromServer<T: Serialize> {
+    server: String,
+    #[serde(flatten)]
+    inner: T,
+}
+
+/// A fresh id, starting with `prefix`, for something handed to the frontend
+/// to answer.
+pub(crate) fn next_id(prefix: &str) -> String {
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    format!("{}-{}", prefix, COUNT.fetch_add(1, Ordering::SeqCst))
+}
+
+pub(crate) fn emit<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app.emit(event, payload) {
+        log::error!("Failed to emit {} event: {}", event, e);
+    }
+}
+
 pub async fn bootstrap(app: AppHandle) -> Result<()> {
     let mut uvx = get_os_specific_command("uvx", &app)?;
     uvx.arg("--help");
@@ -44,72 +142,175 @@ pub async fn bootstrap(app: AppHandle) -> Result<()> {
     Ok(())
 }
 
-pub async fn start(
-    session_id: i32,
-    command: String,
-    args: Vec<String>,
-    env: HashMap<String, String>,
-    app: AppHandle,
-) -> Result<()> {
+pub async fn start(session_id: i32, config: McpServerConfig, app: AppHandle) -> Result<()> {
+    let state = app.state::<State>();
+    let id = state
+        .sessions
+        .lock()
+        .await
+        .entry(session_id)
+        .or_default()
+        .starting(config.label());
+
+    let result = launch(session_id, config, app.clone()).await;
+
+    if let Some(session) = state.sessions.lock().await.get_mut(&session_id) {
+        session.started(id);
+    }
+
+    result
+}
+
+async fn launch(session_id: i32, config: McpServerConfig, app: AppHandle) -> Result<()> {
     let handle = app.clone();
     let state = handle.state::<State>();
-    let server = McpServer::start(command, args, env, app).await?;
+    let server = state.pool.acquire(session_id, config, app.clone()).await?;
     let server_name = server.name().to_string();
 
-    let mut sessions = state.sessions.lock().await;
-    let session = sessions.entry(session_id).or_default();
+    let tools = match server.tools().await {
+        Ok(tools) => tools,
+        Err(e) => {
+            let error = server.logs().annotate(e).await;
+            state.pool.release(session_id, server).await?;
+            return Err(error);
+        }
+    };
 
-    if session.mcp_servers.contains_key(&server_name) {
-        server.kill()?;
-        return Err(anyhow!("A server with the name '{}' is already running in this session.", server_name));
-    }
+    let registered = {
+        let mut sessions = state.sessions.lock().await;
+        let session = sessions.entry(session_id).or_default();
+
+        if session.mcp_servers.contains_key(&server_name) {
+            Err(anyhow!("A server with the name '{}' is already running in this session.", server_name))
+        } else {
+            session.register(server_name.clone(), &server, tools)
+        }
+    };
 
-    let tools = server.tools().await?;
-    for tool in tools {
-        session.tools.insert(tool.name.to_string(), server_name.clone());
+    if let Err(e) = registered {
+        state.pool.release(session_id, server).await?;
+        return Err(e);
     }
 
-    session.mcp_servers.insert(server_name, server);
+    server.attach(session_id, &server_name);
+    supervisor::supervise(session_id, &server, app);
 
     Ok(())
 }
 
 pub async fn stop(session_id: i32, name: String, state: tauri::State<'_, State>) -> Result<()> {
-    let mut sessions = state.sessions.lock().await;
+    let server = {
+        let mut sessions = state.sessions.lock().await;
 
-    let session = sessions.get_mut(&session_id).ok_or_else(|| anyhow!("Session {} not found", session_id))?;
+        let session = sessions.get_mut(&session_id).ok_or_else(|| anyhow!("Session {} not found", session_id))?;
 
-    if let Some(server) = session.mcp_servers.remove(&name) {
-        server.kill()?;
-        Ok(())
-    } else {
-        Err(anyhow!("Server '{}' not found in session {}", name, session_id))
-    }
+        session.deregister(&name)
+            .ok_or_else(|| anyhow!("Server '{}' not found in session {}", name, session_id))?
+    };
+
+    state.pool.release(session_id, server).await
 }
 
 pub async fn stop_session(session_id: i32, state: tauri::State<'_, State>) -> Result<()> {
-    let mut sessions = state.sessions.lock().await;
-
-    if let Some(session) = sessions.remove(&session_id) {
-        for server in session.mcp_servers.values() {
-            server.kill()?;
-        }
+    let session = state.sessions.lock().await.remove(&session_id);
+
+    if let Some(session) = session {
+        // Shut down together, so that slow servers don't hold up the rest.
+        let releases = session
+            .mcp_servers
+            .into_values()
+            .map(|server| state.pool.release(session_id, server));
+
+        futures::future::join_all(releases)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<()>>>()?;
     }
     // This function is designed to succeed even if the session doesn't exist.
     Ok(())
 }
 
-pub async fn get_tools(session_id: i32, state: tauri::State<'_, State>) -> Result<Vec<Tool>> {
+/// Shut down every server in every session, as the app exits.
+pub async fn shutdown(app: &AppHandle) {
+    let state = app.state::<State>();
+    let sessions: Vec<_> = state.sessions.lock().await.drain().collect();
+
+    let releases = sessions.into_iter().flat_map(|(session_id, session)| {
+        let pool = &state.pool;
+        session
+            .mcp_servers
+            .into_iter()
+            .map(move |(name, server)| async move {
+                if let Err(e) = pool.release(session_id, server).await {
+                    log::error!("Failed to shut down MCP server '{}': {}", name, e);
+                }
+            })
+    });
+
+    futures::future::join_all(releases).await;
+}
+
+/// Every server running in every session, with how it's doing. Servers shared
+/// between sessions appear once for each. Those still starting are listed by
+/// what they run, as they've yet to give a name.
+pub async fn running_servers(state: tauri::State<'_, State>) -> Vec<RunningServer> {
     let sessions = state.sessions.lock().await;
 
-    let running_session = match sessions.get(&session_id) {
-        Some(s) => s,
-        None => return Ok(vec![]),
-    };
+    let starting = sessions.iter().flat_map(|(session_id, session)| {
+        session.starting.values().map(|starting| RunningServer {
+            session_id: *session_id,
+            name: starting.label.clone(),
+            status: Status::Starting,
+            pid: None,
+            uptime_ms: starting.since.elapsed().as_millis() as u64,
+            last_error: None,
+            tool_count: 0,
+        })
+    });
+
+    let mut servers: Vec<RunningServer> = sessions
+        .iter()
+        .flat_map(|(session_id, session)| {
+            session.mcp_servers.iter().map(|(name, server)| RunningServer {
+                session_id: *session_id,
+                name: name.clone(),
+                status: server.status(),
+                pid: server.pid().map(|pid| pid.as_u32()),
+                uptime_ms: server.uptime().as_millis() as u64,
+                last_error: server.last_error(),
+                tool_count: session.tools.values().filter(|route| &route.server == name).count(),
+            })
+        })
+        .chain(starting)
+        .collect();
+
+    servers.sort_by(|a, b| (a.session_id, &a.name).cmp(&(b.session_id, &b.name)));
+    servers
+}
+
+/// Replace a session's roots, and let its servers know. Can be called before
+/// any servers are started, so they see the roots from the outset.
+pub async fn set_roots(
+    session_id: i32,
+    roots: Vec<Root>,
+    state: tauri::State<'_, State>,
+) -> Result<()> {
+    state.sessions.lock().await.entry(session_id).or_default().roots = roots;
+
+    for (_, server) in servers(&state, session_id).await {
+        server.notify_roots_changed();
+    }
+
+    Ok(())
+}
 
+pub async fn get_tools(session_id: i32, state: tauri::State<'_, State>) -> Result<Vec<Tool>> {
     let mut tools: Vec<Tool> = vec![];
-    for server in running_session.mcp_servers.values() {
-        tools.extend(server.tools().await?)
+    for (name, server) in servers(&state, session_id).await {
+        for mut tool in server.tools().await? {
+            tool.name = qualify(&name, &tool.name).into();
+            tools.push(tool);
+        }
     }
 
     Ok(tools)
@@ -119,38 +320,435 @@ pub async fn call_tool(
     session_id: i32,
     name: String,
     arguments: serde_json::Map<String, serde_json::Value>,
+    request_id: Option<String>,
+    timeout_ms: Option<u64>,
     state: tauri::State<'_, State>,
-) -> Result<String> {
-    let sessions = state.sessions.lock().await;
+    app: AppHandle,
+) -> Result<ToolResult> {
+    let cancel = CancellationToken::new();
+    if let Some(request_id) = &request_id {
+        state.tool_calls.lock().await.insert(request_id.clone(), cancel.clone());
+    }
 
-    let running_session = sessions.get(&session_id)
-        .ok_or_else(|| anyhow!("Session {} not found", session_id))?;
+    let started = Utc::now();
+    let mut routed: Option<Route> = None;
+
+    let result = async {
+        // Only hold the lock long enough to find the server, so that calls
+        // don't hold each other up.
+        let (route, server) = {
+            let sessions = state.sessions.lock().await;
+
+            let running_session = sessions.get(&session_id)
+                .ok_or_else(|| anyhow!("Session {} not found", session_id))?;
+
+            let route = running_session.resolve(&name)
+                .map_err(|e| anyhow!("{} in session {}", e, session_id))?
+                .clone();
+
+            let server = running_session.mcp_servers.get(&route.server)
+                .ok_or_else(|| anyhow!("MCP Server '{}' not found for tool '{}'", route.server, name))?
+                .clone();
+
+            (route, server)
+        };
+        routed = Some(route.clone());
 
-    let service_name = running_session.tools.get(&name)
-        .ok_or_else(|| anyhow!("Tool '{}' not found in session {}", name, session_id))?
-        .clone();
+        authorize(session_id, &route.server, &route.tool, &arguments, &server, &cancel, &state, &app)
+            .await?;
+
+        let timeout = timeout_ms
+            .or(server.config().timeout_ms)
+            .map(Duration::from_millis);
+
+        let tool_call = CallToolRequestParam {
+            name: std::borrow::Cow::from(route.tool),
+            arguments: Some(arguments.clone()),
+        };
+
+        server
+            .call_tool(&route.server, session_id, tool_call, request_id.clone(), timeout, cancel)
+            .await
+    }
+    .await;
+
+    if let Some(request_id) = &request_id {
+        state.tool_calls.lock().await.remove(request_id);
+    }
+
+    let call = audit::Call {
+        session_id,
+        request_id: request_id.as_deref(),
+        server: routed.as_ref().map(|route| route.server.as_str()),
+        tool: routed.as_ref().map_or(&name, |route| &route.tool),
+        arguments: &arguments,
+        result: match &result {
+            Ok(result) => serde_json::to_value(result).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        },
+        started,
+        finished: Utc::now(),
+    };
+    if let Err(e) = state.audit.record(call).await {
+        log::error!("Failed to record call to '{}' in the audit log: {}", name, e);
+    }
+
+    result
+}
+
+/// Serve metrics for Prometheus on `localhost:port`, or stop serving them if
+/// `port` is `None`.
+pub async fn serve_metrics(port: Option<u16>, state: tauri::State<'_, State>) -> Result<()> {
+    let mut endpoint = state.metrics_endpoint.lock().await;
+    if let Some(ct) = endpoint.take() {
+        ct.cancel();
+    }
+
+    if let Some(port) = port {
+        let ct = CancellationToken::new();
+        metrics::serve(state.metrics.clone(), port, ct.clone()).await?;
+        *endpoint = Some(ct);
+    }
+
+    Ok(())
+}
+
+/// Recorded tool calls matching `filter`, most recent first.
+pub async fn tool_calls(
+    filter: Filter,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<audit::Entry>> {
+    state.audit.query(filter).await
+}
+
+/// Write recorded tool calls matching `filter` to `path`, as JSON Lines.
+pub async fn export_tool_calls(
+    filter: Filter,
+    path: PathBuf,
+    state: tauri::State<'_, State>,
+) -> Result<usize> {
+    state.audit.export(filter, path).await
+}
+
+/// Check a call against the policy, holding it for the user's approval if
+/// that's what the policy asks for.
+#[allow(clippy::too_many_arguments)]
+async fn authorize(
+    session_id: i32,
+    server_name: &str,
+    tool: &str,
+    arguments: &serde_json::Map<String, serde_json::Value>,
+    server: &McpServer,
+    cancel: &CancellationToken,
+    state: &State,
+    app: &AppHandle,
+) -> Result<()> {
+    let annotations = server.annotations(tool).unwrap_or_default();
+    let action = state.policy.lock().await.check(server_name, tool, &annotations, arguments);
 
-    let server = running_session.mcp_servers.get(&service_name)
-        .ok_or_else(|| anyhow!("MCP Server '{}' not found for tool '{}'", service_name, name))?;
+    match action {
+        Action::Allow => return Ok(()),
+        Action::Deny => return Err(PolicyError::Denied(tool.to_string()).into()),
+        Action::Ask => {}
+    }
 
-    let tool_call = CallToolRequestParam {
-        name: std::borrow::Cow::from(name),
-        arguments: Some(arguments),
+    // Tool calls needn't have ids of their own.
+    let id = next_id("approval");
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state.tool_approvals.lock().await.insert(id.clone(), tx);
+
+    emit(
+        app,
+        "mcp/tool-approval",
+        ToolApproval {
+            id: id.clone(),
+            session_id,
+            server: server_name.to_string(),
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            annotations,
+        },
+    );
+
+    // Waits on the user, so there's no timeout, but the call may be
+    // cancelled in the meantime.
+    let approved = tokio::select! {
+        approved = rx => approved.unwrap_or(false),
+        _ = cancel.cancelled() => {
+            state.tool_approvals.lock().await.remove(&id);
+            emit(app, "mcp/tool-approval-cancelled", ToolApprovalCancelled { id });
+            return Err(CallError::Cancelled.into());
+        }
     };
 
-    server.call_tool(tool_call).await
+    if approved {
+        Ok(())
+    } else {
+        Err(PolicyError::Rejected(tool.to_string()).into())
+    }
+}
+
+/// Replace the rules tool calls are checked against.
+pub async fn set_policy(rules: Vec<PolicyRule>, state: tauri::State<'_, State>) -> Result<()> {
+    *state.policy.lock().await = Policy::new(rules)?;
+    Ok(())
+}
+
+/// Answer a call held for approval, once the user has made up their mind.
+pub async fn respond_to_tool_approval(
+    id: String,
+    approved: bool,
+    state: tauri::State<'_, State>,
+) -> Result<()> {
+    let tx = state.tool_approvals.lock().await.remove(&id)
+        .ok_or_else(|| anyhow!("No tool call awaiting approval with id '{}'", id))?;
+
+    // The call may have been cancelled in the meantime.
+    let _ = tx.send(approved);
+    Ok(())
+}
+
+pub async fn cancel_tool_call(request_id: String, state: tauri::State<'_, State>) -> Result<()> {
+    let cancel = state.tool_calls.lock().await.remove(&request_id)
+        .ok_or_else(|| anyhow!("No tool call in progress with id '{}'", request_id))?;
+
+    cancel.cancel();
+    Ok(())
+}
+
+/// Answer a server's sampling request, once the frontend has dealt with it.
+pub async fn respond_to_sampling(
+    id: String,
+    response: SamplingResponse,
+    state: tauri::State<'_, State>,
+) -> Result<()> {
+    let tx = state.sampling.lock().await.remove(&id)
+        .ok_or_else(|| anyhow!("No sampling request pending with id '{}'", id))?;
+
+    // The server may have given up in the meantime.
+    let _ = tx.send(response);
+    Ok(())
+}
+
+pub async fn logs(
+    session_id: i32,
+    name: String,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<String>> {
+    let server = get_server(&state, session_id, &name).await?;
+    Ok(server.logs().lines())
+}
+
+pub async fn set_log_level(
+    session_id: i32,
+    server: String,
+    level: LoggingLevel,
+    state: tauri::State<'_, State>,
+) -> Result<()> {
+    let server = get_server(&state, session_id, &server).await?;
+    server.set_log_level(level).await
+}
+
+pub async fn list_resources(
+    session_id: i32,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<FromServer<Resource>>> {
+    let mut resources = vec![];
+    for (name, server) in servers(&state, session_id).await {
+        if !server.supports_resources() {
+            continue;
+        }
+        for resource in server.resources().await? {
+            resources.push(FromServer {
+                server: name.clone(),
+                inner: resource,
+            });
+        }
+    }
+
+    Ok(resources)
+}
+
+pub async fn list_resource_templates(
+    session_id: i32,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<FromServer<ResourceTemplate>>> {
+    let mut templates = vec![];
+    for (name, server) in servers(&state, session_id).await {
+        if !server.supports_resources() {
+            continue;
+        }
+        for template in server.resource_templates().await? {
+            templates.push(FromServer {
+                server: name.clone(),
+                inner: template,
+            });
+        }
+    }
+
+    Ok(templates)
+}
+
+pub async fn read_resource(
+    session_id: i32,
+    server: String,
+    uri: String,
+    state: tauri::State<'_, State>,
+) -> Result<ReadResourceResult> {
+    let server = get_server(&state, session_id, &server).await?;
+    server.read_resource(uri).await
+}
+
+pub async fn subscribe_resource(
+    session_id: i32,
+    server: String,
+    uri: String,
+    state: tauri::State<'_, State>,
+) -> Result<()> {
+    let server = get_server(&state, session_id, &server).await?;
+    server.subscribe(uri).await
+}
+
+pub async fn unsubscribe_resource(
+    session_id: i32,
+    server: String,
+    uri: String,
+    state: tauri::State<'_, State>,
+) -> Result<()> {
+    let server = get_server(&state, session_id, &server).await?;
+    server.unsubscribe(uri).await
+}
+
+pub async fn list_prompts(
+    session_id: i32,
+    state: tauri::State<'_, State>,
+) -> Result<Vec<FromServer<Prompt>>> {
+    let mut prompts = vec![];
+    for (name, server) in servers(&state, session_id).await {
+        if !server.supports_prompts() {
+            continue;
+        }
+        for prompt in server.prompts().await? {
+            prompts.push(FromServer {
+                server: name.clone(),
+                inner: prompt,
+            });
+        }
+    }
+
+    Ok(prompts)
+}
+
+pub async fn get_prompt(
+    session_id: i32,
+    server: String,
+    name: String,
+    arguments: HashMap<String, String>,
+    state: tauri::State<'_, State>,
+) -> Result<PromptResult> {
+    let server = get_server(&state, session_id, &server).await?;
+
+    // Prompt arguments are always strings, per the spec.
+    let arguments = arguments
+        .into_iter()
+        .map(|(k, v)| (k, serde_json::Value::String(v)))
+        .collect();
+
+    server.get_prompt(name, arguments).await
+}
+
+/// The servers running in a session, as handles that can be used once the
+/// sessions lock is released.
+async fn servers(state: &State, session_id: i32) -> Vec<(String, Arc<McpServer>)> {
+    state
+        .sessions
+        .lock()
+        .await
+        .get(&session_id)
+        .map(|session| {
+            session
+                .mcp_servers
+                .iter()
+                .map(|(name, server)| (name.clone(), server.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
+async fn get_server(state: &State, session_id: i32, name: &str) -> Result<Arc<McpServer>> {
+    state
+        .sessions
+        .lock()
+        .await
+        .get(&session_id)
+        .ok_or_else(|| anyhow!("Session {} not found", session_id))?
+        .mcp_servers
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Server '{}' not found in session {}", name, session_id))
+}
+
+/// What the server `config` runs says about itself, along with its tools.
+///
+/// Comes from a session's server if one is running it already. Otherwise it's
+/// cached, so that the server only has to be started the first time round,
+/// and only once however many ask at the same time.
 pub async fn peer_info(
-    command: String,
-    args: Vec<String>,
-    env: HashMap<String, String>,
+    config: McpServerConfig,
+    state: tauri::State<'_, State>,
     app: AppHandle,
 ) -> Result<String> {
-    let server = McpServer::start(command, args, env, app).await?;
-    let peer_info = server.peer_info();
-    server.kill()?;
-    Ok(serde_json::to_string(&peer_info)?)
+    let metadata = match running(&state, &config).await {
+        Some(server) => {
+            let metadata = Metadata {
+                peer_info: server.peer_info(),
+                tools: server.tools().await?,
+            };
+            state.metadata.insert(&config, metadata.clone());
+            metadata
+        }
+        None => {
+            state
+                .metadata
+                .get_or_fetch(&config, || async {
+                    let server = McpServer::start(config.clone(), app).await?;
+                    let tools = server.tools().await;
+                    server.kill()?;
+
+                    let tools = match tools {
+                        Ok(tools) => tools,
+                        Err(e) => return Err(server.logs().annotate(e).await),
+                    };
+                    Ok(Metadata {
+                        peer_info: server.peer_info(),
+                        tools,
+                    })
+                })
+                .await?
+        }
+    };
+
+    Ok(serde_json::to_string(&metadata)?)
+}
+
+/// Forget what servers have said about themselves, so that it's asked for
+/// again. Only that of the server `config` runs, if given.
+pub fn invalidate_peer_info(config: Option<McpServerConfig>, state: tauri::State<'_, State>) {
+    state.metadata.invalidate(config.as_ref());
+}
+
+/// A live server, in any session, running what `config` does.
+async fn running(state: &State, config: &McpServerConfig) -> Option<Arc<McpServer>> {
+    let key = metadata::key(config);
+
+    state
+        .sessions
+        .lock()
+        .await
+        .values()
+        .flat_map(|session| session.mcp_servers.values())
+        .find(|server| metadata::key(server.config()) == key && server.is_alive())
+        .cloned()
 }
 
 pub async fn rename_server(
@@ -159,20 +757,33 @@ pub async fn rename_server(
     new_name: String,
     state: tauri::State<'_, State>,
 ) -> Result<()> {
+    // Listing tools can take a while, and shouldn't hold up other sessions.
+    let server = get_server(&state, session_id, &old_name).await?;
+    let tools: Vec<Tool> = server.tools().await?;
+
     let mut sessions = state.sessions.lock().await;
 
     let session = sessions.get_mut(&session_id)
         .ok_or_else(|| anyhow!("Session {} not found", session_id))?;
 
-    if let Some(server) = session.mcp_servers.remove(&old_name) {
-        for (_, server_name) in session.tools.iter_mut() {
-            if *server_name == old_name {
-                *server_name = new_name.clone();
-            }
-        }
-        session.mcp_servers.insert(new_name, server);
-        Ok(())
-    } else {
-        Err(anyhow!("Server '{}' not found in session {}", old_name, session_id))
+    if session.mcp_servers.contains_key(&new_name) {
+        return Err(anyhow!("A server with the name '{}' is already running in this session.", new_name));
     }
+
+    // Stopped, renamed or restarted while we were listing its tools.
+    if !session.mcp_servers.get(&old_name).is_some_and(|current| Arc::ptr_eq(current, &server)) {
+        return Err(anyhow!("Server '{}' not found in session {}", old_name, session_id));
+    }
+
+    // Qualified names follow the server's name, so route its tools afresh,
+    // putting things back as they were if the new names are taken.
+    session.deregister(&old_name);
+
+    if let Err(e) = session.register(new_name.clone(), &server, tools.clone()) {
+        session.register(old_name, &server, tools)?;
+        return Err(e);
+    }
+
+    server.attach(session_id, &new_name);
+    Ok(())
 }
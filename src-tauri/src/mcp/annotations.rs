@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Hints a server gives about what its tools do. Untrusted, unless the server
+/// is.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    pub title: Option<String>,
+    pub read_only_hint: Option<bool>,
+    pub destructive_hint: Option<bool>,
+    pub idempotent_hint: Option<bool>,
+    pub open_world_hint: Option<bool>,
+}
+
+/// The annotations of a server's tools, by tool name.
+///
+/// rmcp's `Tool` predates annotations and drops them when listing tools, so
+/// they're picked out of `tools/list` responses as they come off the wire.
+#[derive(Debug, Clone, Default)]
+pub struct Annotations(Arc<Mutex<HashMap<String, ToolAnnotations>>>);
+
+impl Annotations {
+    pub fn get(&self, tool: &str) -> Option<ToolAnnotations> {
+        self.0.lock().ok()?.get(tool).cloned()
+    }
+
+    /// Parse an incoming message, or batch of them, noting any annotations
+    /// on the way.
+    pub fn parse<T: DeserializeOwned>(&self, data: &[u8]) -> serde_json::Result<T> {
+        let value: Value = serde_json::from_slice(data)?;
+        self.capture(&value);
+        serde_json::from_value(value)
+    }
+
+    fn capture(&self, value: &Value) {
+        if let Value::Array(messages) = value {
+            messages.iter().for_each(|message| self.capture(message));
+            return;
+        }
+
+        let Some(tools) = value["result"]["tools"].as_array() else {
+            return;
+        };
+
+        let Ok(mut annotations) = self.0.lock() else {
+            return;
+        };
+
+        for tool in tools {
+            let Some(name) = tool["name"].as_str() else {
+                continue;
+            };
+
+            match serde_json::from_value(tool["annotations"].clone()) {
+                Ok(tool_annotations) => annotations.insert(name.to_string(), tool_annotations),
+                Err(_) => annotations.remove(name),
+            };
+        }
+    }
+}
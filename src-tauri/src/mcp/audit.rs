@@ -0,0 +1,243 @@
+//! A lasting record of every tool call, kept in the `tool_calls` table.
+//!
+//! The table is created by the frontend's migrations, like every other, but
+//! written straight from here, so that no call goes unrecorded whatever the
+//! frontend is up to.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// How timestamps are stored. Close enough to SQLite's own `CURRENT_TIMESTAMP`
+/// to compare against it, but down to the millisecond.
+const TIMESTAMP: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// How long to wait on the frontend's own writes before giving up on one of
+/// ours.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub id: i64,
+    pub session_id: i32,
+    /// The app run the session belongs to, if it was started by an app.
+    pub app_run_id: Option<i64>,
+    pub request_id: Option<String>,
+    /// Unknown when the tool couldn't be found.
+    pub server: Option<String>,
+    pub tool: String,
+    pub arguments: Value,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub started: String,
+    pub finished: String,
+}
+
+/// A tool call to record, once it's over.
+#[derive(Debug)]
+pub struct Call<'a> {
+    pub session_id: i32,
+    pub request_id: Option<&'a str>,
+    pub server: Option<&'a str>,
+    pub tool: &'a str,
+    pub arguments: &'a Map<String, Value>,
+    pub result: Result<Value, String>,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+}
+
+/// Which entries to fetch. Everything left out matches anything.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    pub session_id: Option<i32>,
+    pub app_run_id: Option<i64>,
+    pub server: Option<String>,
+    pub tool: Option<String>,
+    /// Only calls that failed, or only those that didn't.
+    pub failed: Option<bool>,
+    /// Bounds on when calls started, in any format SQLite's `datetime`
+    /// understands.
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// SQLite blocks, so everything here runs on the blocking pool, off a clone
+/// sharing the one connection.
+#[derive(Debug, Clone)]
+pub struct Audit {
+    path: PathBuf,
+    /// Opened on first use, by which time the migrations have run.
+    conn: Arc<Mutex<Option<Connection>>>,
+}
+
+impl Audit {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            conn: Default::default(),
+        }
+    }
+
+    pub async fn record(&self, call: Call<'_>) -> Result<()> {
+        let (result, error) = match call.result {
+            Ok(result) => (Some(result.to_string()), None),
+            Err(error) => (None, Some(error)),
+        };
+        let duration = (call.finished - call.started).num_milliseconds();
+        let session_id = call.session_id;
+        let request_id = call.request_id.map(str::to_string);
+        let server = call.server.map(str::to_string);
+        let tool = call.tool.to_string();
+        let arguments = serde_json::to_string(call.arguments)?;
+        let started = call.started.format(TIMESTAMP).to_string();
+        let finished = call.finished.format(TIMESTAMP).to_string();
+
+        self.blocking(move |audit| {
+            audit.with_conn(|conn| {
+                // Apps run in sessions of their own, so a session has at most one
+                // run.
+                let app_run_id: Option<i64> = conn
+                    .query_row(
+                        "SELECT id FROM app_runs WHERE session_id = ?1",
+                        [session_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                conn.execute(
+                    "INSERT INTO tool_calls (
+                        session_id, app_run_id, request_id, server, tool, arguments,
+                        result, error, duration_ms, started, finished
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        session_id, app_run_id, request_id, server, tool, arguments, result, error,
+                        duration, started, finished,
+                    ],
+                )?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Entries matching `filter`, most recent first.
+    pub async fn query(&self, filter: Filter) -> Result<Vec<Entry>> {
+        self.blocking(move |audit| audit.select(&filter)).await
+    }
+
+    /// Write the entries matching `filter` to `path` as JSON Lines, oldest
+    /// first, returning how many there were.
+    pub async fn export(&self, filter: Filter, path: PathBuf) -> Result<usize> {
+        self.blocking(move |audit| {
+            let entries = audit.select(&filter)?;
+
+            let mut file = BufWriter::new(File::create(&path)?);
+            for entry in entries.iter().rev() {
+                serde_json::to_writer(&mut file, entry)?;
+                file.write_all(b"\n")?;
+            }
+            file.flush()?;
+
+            Ok(entries.len())
+        })
+        .await
+    }
+
+    fn select(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, session_id, app_run_id, request_id, server, tool, arguments,
+                        result, error, duration_ms, started, finished
+                FROM tool_calls
+                WHERE (?1 IS NULL OR session_id = ?1)
+                    AND (?2 IS NULL OR app_run_id = ?2)
+                    AND (?3 IS NULL OR server = ?3)
+                    AND (?4 IS NULL OR tool = ?4)
+                    AND (?5 IS NULL OR (error IS NOT NULL) = ?5)
+                    AND (?6 IS NULL OR started >= datetime(?6))
+                    AND (?7 IS NULL OR started < datetime(?7))
+                ORDER BY id DESC
+                LIMIT ?8 OFFSET ?9",
+            )?;
+
+            let entries = stmt
+                .query_map(
+                    params![
+                        filter.session_id,
+                        filter.app_run_id,
+                        filter.server,
+                        filter.tool,
+                        filter.failed,
+                        filter.since,
+                        filter.until,
+                        filter.limit.map_or(-1, i64::from),
+                        filter.offset.unwrap_or(0),
+                    ],
+                    entry,
+                )?
+                .collect::<rusqlite::Result<Vec<Entry>>>()?;
+
+            Ok(entries)
+        })
+    }
+
+    /// Run `f` on the blocking pool.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Audit) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let audit = self.clone();
+        tokio::task::spawn_blocking(move || f(&audit)).await?
+    }
+
+    fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|_| anyhow!("Audit log connection poisoned"))?;
+
+        if conn.is_none() {
+            let opened = Connection::open(&self.path)?;
+            // The frontend writes to the same database, so wait out its locks
+            // rather than failing, and let it read while we write.
+            opened.busy_timeout(BUSY_TIMEOUT)?;
+            opened.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+            *conn = Some(opened);
+        }
+
+        f(conn.as_ref().unwrap())
+    }
+}
+
+fn entry(row: &Row) -> rusqlite::Result<Entry> {
+    let json = |value: Option<String>| value.and_then(|value| serde_json::from_str(&value).ok());
+
+    Ok(Entry {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        app_run_id: row.get(2)?,
+        request_id: row.get(3)?,
+        server: row.get(4)?,
+        tool: row.get(5)?,
+        arguments: json(row.get(6)?).unwrap_or_default(),
+        result: json(row.get(7)?),
+        error: row.get(8)?,
+        duration_ms: row.get(9)?,
+        started: row.get(10)?,
+        finished: row.get(11)?,
+    })
+}
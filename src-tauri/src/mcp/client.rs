@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rmcp::model::{
+    ClientInfo, CreateMessageRequestMethod, CreateMessageRequestParam, CreateMessageResult,
+    JsonObject, ListRootsResult, LoggingLevel, LoggingMessageNotificationParam,
+    ProgressNotificationParam, RequestId, ResourceUpdatedNotificationParam, Root,
+    RootsCapabilities, Tool,
+};
+use rmcp::service::{Peer, RequestContext, RoleClient};
+use rmcp::{ClientHandler, Error as McpError};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use super::config::SamplingConfig;
+use super::{emit, next_id};
+use super::sampling::{SamplingCancelled, SamplingRequest, SamplingResponse};
+use crate::state::{qualify, State};
+
+/// A tool call in flight.
+#[derive(Debug, Clone)]
+struct Call {
+    session_id: i32,
+    id: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerEvent<T: Serialize + Clone> {
+    session_id: Option<i32>,
+    server: Option<String>,
+    #[serde(flatten)]
+    data: T,
+}
+
+#[derive(Clone, Serialize)]
+struct ResourceUpdated {
+    uri: String,
+}
+
+#[derive(Clone, Serialize)]
+struct ToolsChanged {
+    tools: Vec<Tool>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    tool_call_id: Option<String>,
+    progress: u32,
+    total: Option<u32>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogMessage {
+    tool_call_id: Option<String>,
+    level: LoggingLevel,
+    logger: Option<String>,
+    data: serde_json::Value,
+}
+
+/// Our side of the MCP connection: handles the requests and notifications a
+/// server sends us, relaying them to the frontend as events.
+#[derive(Debug)]
+pub struct McpClient {
+    app: AppHandle,
+    /// The sessions the server is attached to, and the name it goes by in
+    /// each of them.
+    sessions: Arc<Mutex<HashMap<i32, String>>>,
+    /// Tool calls in flight, by the id of the request carrying them.
+    calls: Arc<Mutex<HashMap<RequestId, Call>>>,
+    /// The server's tools, as of the last time we listed them.
+    tools: Arc<Mutex<Option<Vec<Tool>>>>,
+    /// Set if the server may ask us for completions.
+    sampling: Option<SamplingConfig>,
+    peer: Option<Peer<RoleClient>>,
+}
+
+impl McpClient {
+    pub fn new(app: AppHandle, sampling: Option<SamplingConfig>) -> Self {
+        Self {
+            app,
+            sessions: Default::default(),
+            calls: Default::default(),
+            tools: Default::default(),
+            sampling,
+            peer: None,
+        }
+    }
+
+    /// Tag future events with the session and name the server is running as.
+    /// Shared servers are attached to several sessions at once.
+    pub fn attach(&self, session_id: i32, server: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(session_id, server.to_string());
+        }
+
+        // The server's roots are those of every session it's attached to.
+        self.notify_roots_changed();
+    }
+
+    pub fn detach(&self, session_id: i32) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(&session_id);
+        }
+
+        self.notify_roots_changed();
+    }
+
+    /// Tell the server to list its roots again.
+    pub fn notify_roots_changed(&self) {
+        let Some(peer) = self.peer.clone() else {
+            return;
+        };
+
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = peer.notify_roots_list_changed().await {
+                log::debug!("Failed to notify MCP server of changed roots: {}", e);
+            }
+        });
+    }
+
+    fn sessions(&self) -> HashMap<i32, String> {
+        self.sessions
+            .lock()
+            .map(|sessions| sessions.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn cached_tools(&self) -> Option<Vec<Tool>> {
+        self.tools.lock().ok()?.clone()
+    }
+
+    pub fn cache_tools(&self, tools: Vec<Tool>) {
+        if let Ok(mut cached) = self.tools.lock() {
+            *cached = Some(tools);
+        }
+    }
+
+    /// Attribute notifications about request `id` to tool call `call_id`,
+    /// made from session `session_id`.
+    pub fn track(&self, id: RequestId, session_id: i32, call_id: String) {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.insert(
+                id,
+                Call {
+                    session_id,
+                    id: call_id,
+                },
+            );
+        }
+    }
+
+    pub fn untrack(&self, id: &RequestId) {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.remove(id);
+        }
+    }
+
+    fn call(&self, id: &RequestId) -> Option<Call> {
+        self.calls.lock().ok()?.get(id).cloned()
+    }
+
+    /// Log messages don't say which request they're about, so the best we can
+    /// do is pin them on the only call in flight, if there is one.
+    fn current_call(&self) -> Option<Call> {
+        let calls = self.calls.lock().ok()?;
+        match calls.len() {
+            1 => calls.values().next().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Work out which session a request from the server is on behalf of:
+    /// the one making the only call in flight, or failing that, the only one
+    /// the server is attached to.
+    fn requester(&self) -> Option<(i32, Option<Call>)> {
+        if let Some(call) = self.current_call() {
+            return Some((call.session_id, Some(call)));
+        }
+
+        let sessions = self.sessions();
+        match sessions.len() {
+            1 => sessions
+                .into_keys()
+                .next()
+                .map(|session_id| (session_id, None)),
+            _ => None,
+        }
+    }
+
+    /// Emit `event` once for every session the server is attached to.
+    pub(super) fn emit<T: Serialize + Clone>(&self, event: &str, data: T) {
+        let sessions = self.sessions();
+
+        if sessions.is_empty() {
+            emit(
+                &self.app,
+                event,
+                ServerEvent {
+                    session_id: None,
+                    server: None,
+                    data,
+                },
+            );
+            return;
+        }
+
+        for (session_id, server) in sessions {
+            self.emit_to(session_id, Some(server), event, data.clone());
+        }
+    }
+
+    fn emit_to<T: Serialize + Clone>(
+        &self,
+        session_id: i32,
+        server: Option<String>,
+        event: &str,
+        data: T,
+    ) {
+        emit(
+            &self.app,
+            event,
+            ServerEvent {
+                session_id: Some(session_id),
+                server,
+                data,
+            },
+        );
+    }
+
+    /// Emit an event about tool call `call` to the session that made it, or to
+    /// every session if we don't know which call it's about.
+    fn emit_for<T: Serialize + Clone>(&self, call: Option<Call>, event: &str, data: T) {
+        match call {
+            Some(call) => {
+                let server = self.sessions().remove(&call.session_id);
+                self.emit_to(call.session_id, server, event, data);
+            }
+            None => self.emit(event, data),
+        }
+    }
+}
+
+impl ClientHandler for McpClient {
+    async fn create_message(
+        &self,
+        params: CreateMessageRequestParam,
+        context: RequestContext<RoleClient>,
+    ) -> Result<CreateMessageResult, McpError> {
+        let Some(config) = self.sampling.clone() else {
+            return Err(McpError::method_not_found::<CreateMessageRequestMethod>());
+        };
+
+        let Some((session_id, call)) = self.requester() else {
+            return Err(McpError::invalid_request(
+                "Can't tell which session the sampling request is for",
+                None,
+            ));
+        };
+
+        // Servers pick their own request ids, so theirs can't be used without
+        // clashing.
+        let id = next_id("sampling");
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        let state = self.app.state::<State>();
+        state.sampling.lock().await.insert(id.clone(), tx);
+
+        let server = self.sessions().remove(&session_id);
+        self.emit_to(
+            session_id,
+            server.clone(),
+            "mcp/sampling",
+            SamplingRequest {
+                id: id.clone(),
+                tool_call_id: call.map(|call| call.id),
+                sampling: config,
+                request: params,
+            },
+        );
+
+        // Waits on the user, so there's no timeout, but the server may give
+        // up on us, or go away.
+        let response = tokio::select! {
+            response = rx => response.ok(),
+            _ = context.ct.cancelled() => None,
+        };
+
+        match response {
+            Some(SamplingResponse::Result(result)) => Ok(result),
+            Some(SamplingResponse::Error(e)) => Err(McpError::internal_error(e, None)),
+            None => {
+                state.sampling.lock().await.remove(&id);
+                self.emit_to(
+                    session_id,
+                    server,
+                    "mcp/sampling-cancelled",
+                    SamplingCancelled { id },
+                );
+                Err(McpError::internal_error(
+                    "Sampling request was cancelled",
+                    None,
+                ))
+            }
+        }
+    }
+
+    async fn list_roots(
+        &self,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<ListRootsResult, McpError> {
+        let state = self.app.state::<State>();
+        let sessions = state.sessions.lock().await;
+
+        // Servers are only shared between sessions with the same roots, but
+        // those can change afterwards, so only roots every session has are
+        // offered. Anything else would let one session's model reach into
+        // another's directories.
+        let mut attached = self
+            .sessions()
+            .into_keys()
+            .map(|session_id| sessions.get(&session_id).map(|session| &session.roots));
+
+        let mut roots: Vec<Root> = attached.next().flatten().cloned().unwrap_or_default();
+        for other in attached {
+            roots.retain(|root| other.is_some_and(|other| other.iter().any(|r| r.uri == root.uri)));
+        }
+
+        Ok(ListRootsResult { roots })
+    }
+
+    async fn on_progress(&self, params: ProgressNotificationParam) {
+        // We use request ids as progress tokens.
+        let call = self.call(&params.progress_token);
+
+        self.emit_for(
+            call.clone(),
+            "mcp/progress",
+            Progress {
+                tool_call_id: call.map(|call| call.id),
+                progress: params.progress,
+                total: params.total,
+            },
+        );
+    }
+
+    async fn on_logging_message(&self, params: LoggingMessageNotificationParam) {
+        let call = self.current_call();
+
+        self.emit_for(
+            call.clone(),
+            "mcp/message",
+            LogMessage {
+                tool_call_id: call.map(|call| call.id),
+                level: params.level,
+                logger: params.logger,
+                data: params.data,
+            },
+        );
+    }
+
+    async fn on_tool_list_changed(&self) {
+        let Some(peer) = self.peer.clone() else {
+            return;
+        };
+
+        let tools = match peer.list_all_tools().await {
+            Ok(tools) => tools,
+            Err(e) => {
+                log::error!("Failed to refresh tools: {}", e);
+                return;
+            }
+        };
+
+        self.cache_tools(tools.clone());
+
+        for (session_id, server) in self.sessions() {
+            {
+                let state = self.app.state::<State>();
+                let mut sessions = state.sessions.lock().await;
+
+                let Some(session) = sessions.get_mut(&session_id) else {
+                    continue;
+                };
+
+                // The server may have been stopped while we were listing.
+                if !session.mcp_servers.contains_key(&server) {
+                    continue;
+                }
+
+                if let Err(e) = session.route(&server, &tools) {
+                    log::error!("Failed to refresh tools for {}: {}", server, e);
+                    continue;
+                }
+            }
+
+            let mut tools = tools.clone();
+            for tool in tools.iter_mut() {
+                tool.name = qualify(&server, &tool.name).into();
+            }
+
+            self.emit_to(
+                session_id,
+                Some(server),
+                "mcp/tools-changed",
+                ToolsChanged { tools },
+            );
+        }
+    }
+
+    async fn on_resource_updated(&self, params: ResourceUpdatedNotificationParam) {
+        self.emit("mcp/resource-updated", ResourceUpdated { uri: params.uri });
+    }
+
+    async fn on_resource_list_changed(&self) {
+        self.emit("mcp/resources-changed", ());
+    }
+
+    async fn on_prompt_list_changed(&self) {
+        self.emit("mcp/prompts-changed", ());
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        let mut info = ClientInfo::default();
+        info.capabilities.roots = Some(RootsCapabilities {
+            list_changed: Some(true),
+        });
+        if self.sampling.is_some() {
+            info.capabilities.sampling = Some(JsonObject::default());
+        }
+        info
+    }
+
+    fn get_peer(&self) -> Option<Peer<RoleClient>> {
+        self.peer.clone()
+    }
+
+    fn set_peer(&mut self, peer: Peer<RoleClient>) {
+        self.peer = Some(peer);
+    }
+}
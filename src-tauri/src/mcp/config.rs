@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportKind {
+    /// A local child process speaking JSON-RPC over stdin/stdout.
+    #[default]
+    Stdio,
+    /// A remote server using the legacy HTTP+SSE transport.
+    Sse,
+    /// A remote server using the Streamable HTTP transport.
+    StreamableHttp,
+}
+
+/// Everything needed to launch (or connect to) an MCP server. Mirrors a row in
+/// the `mcp_servers` table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerConfig {
+    #[serde(default)]
+    pub transport: TransportKind,
+    #[serde(default)]
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Working directory of local servers. Defaults to our own.
+    pub cwd: Option<String>,
+    /// Run the server in a container. `args` are passed to the image.
+    pub container: Option<ContainerConfig>,
+    /// Confine a local server. Left unset, it runs with all of our
+    /// privileges.
+    pub sandbox: Option<SandboxConfig>,
+    /// Cap the resources a local server can use.
+    pub limits: Option<ResourceLimits>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub bearer_token: Option<String>,
+    /// Restart the server if it crashes. Left unset, crashed servers are
+    /// simply dropped from their session.
+    pub restart: Option<RestartPolicy>,
+    /// Let the server ask us for LLM completions. Left unset, its sampling
+    /// requests are refused.
+    pub sampling: Option<SamplingConfig>,
+    /// Give up on tool calls that take longer than this. Can be overridden
+    /// per call.
+    pub timeout_ms: Option<u64>,
+    /// How long a local server has to exit once asked to, before it's
+    /// killed.
+    pub shutdown_grace_ms: Option<u64>,
+    /// Give every session its own instance of the server, rather than sharing
+    /// one between all sessions using the same command. For servers that keep
+    /// state about their client.
+    #[serde(default)]
+    pub isolated: bool,
+}
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContainerConfig {
+    /// The container runtime, ie. `docker` or `podman`, or a path to one.
+    pub runtime: String,
+    pub image: String,
+    /// Mounts, in the runtime's `host:container[:options]` form.
+    pub volumes: Vec<String>,
+    /// Variables from our own environment to pass through to the container,
+    /// on top of the server's `env`.
+    pub pass_env: Vec<String>,
+    pub network: Option<String>,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            runtime: "docker".to_string(),
+            image: String::new(),
+            volumes: vec![],
+            pass_env: vec![],
+            network: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SandboxConfig {
+    /// Paths the server may read, on top of the system's own.
+    pub read: Vec<String>,
+    /// Paths the server may read and write.
+    pub write: Vec<String>,
+    /// Run the server in a network namespace of its own, with no way out.
+    pub no_network: bool,
+    /// Kill the server if it makes system calls it has no business making.
+    pub seccomp: bool,
+}
+
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResourceLimits {
+    pub memory_mb: Option<u64>,
+    /// Total CPU time, rather than a share of the CPU.
+    pub cpu_seconds: Option<u64>,
+    pub open_files: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SamplingConfig {
+    /// The engine and model to sample with. Left unset, the session's own
+    /// are used.
+    pub engine_id: Option<i64>,
+    pub model: Option<String>,
+    /// Tokens the server may spend per session. Requests are charged the
+    /// `maxTokens` they ask for, as engines don't all report usage.
+    pub budget: Option<u64>,
+    /// Sample without asking the user first.
+    pub auto_approve: bool,
+}
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RestartPolicy {
+    pub max_restarts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Exponential backoff before the `attempt`th (zero-indexed) restart.
+    pub fn backoff(&self, attempt: u32) -> std::time::Duration {
+        let ms = self
+            .initial_backoff_ms
+            .saturating_mul(2u64.saturating_pow(attempt))
+            .min(self.max_backoff_ms);
+        std::time::Duration::from_millis(ms)
+    }
+}
+
+/// How long servers have to exit, unless they say otherwise.
+const SHUTDOWN_GRACE_MS: u64 = 5000;
+
+impl McpServerConfig {
+    pub fn is_remote(&self) -> bool {
+        self.transport != TransportKind::Stdio
+    }
+
+    /// What to call the server before it's said what it's called.
+    pub fn label(&self) -> String {
+        match (&self.container, &self.url) {
+            (_, Some(url)) if self.is_remote() => url.clone(),
+            (Some(container), _) => container.image.clone(),
+            _ => self.command.clone(),
+        }
+    }
+
+    pub fn shutdown_grace(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.shutdown_grace_ms.unwrap_or(SHUTDOWN_GRACE_MS))
+    }
+
+    pub fn url(&self) -> Result<reqwest::Url> {
+        let url = self
+            .url
+            .as_deref()
+            .ok_or_else(|| anyhow!("{:?} servers require a url", self.transport))?;
+
+        reqwest::Url::parse(url).map_err(|e| anyhow!("Invalid server url '{}': {}", url, e))
+    }
+
+    /// Builds the HTTP client used by remote transports, with any configured
+    /// auth baked in as default headers.
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let mut headers = HeaderMap::new();
+
+        for (k, v) in self.headers.iter() {
+            let header_name = HeaderName::from_bytes(k.as_bytes())
+                .map_err(|e| anyhow!("Invalid header name '{}': {}", k, e))?;
+            let header_value = HeaderValue::from_str(v)
+                .map_err(|e| anyhow!("Invalid header value for '{}': {}", k, e))?;
+            headers.insert(header_name, header_value);
+        }
+
+        if let Some(token) = &self.bearer_token {
+            let mut header_value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|e| anyhow!("Invalid bearer token: {}", e))?;
+            header_value.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, header_value);
+        }
+
+        Ok(reqwest::Client::builder().default_headers(headers).build()?)
+    }
+}
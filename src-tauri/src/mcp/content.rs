@@ -0,0 +1,154 @@
+use rmcp::model::{
+    CallToolResult, GetPromptResult, PromptMessageContent, PromptMessageRole, RawContent,
+    RawTextContent, ResourceContents,
+};
+use serde::Serialize;
+
+/// A single piece of a tool's output. Binary data (images, blobs) is passed
+/// through base64 encoded, as it arrives from the server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ToolContent {
+    #[serde(rename_all = "camelCase")]
+    Text { text: String },
+
+    #[serde(rename_all = "camelCase")]
+    Image { data: String, mime_type: String },
+
+    #[serde(rename_all = "camelCase")]
+    Resource {
+        uri: String,
+        mime_type: Option<String>,
+        text: Option<String>,
+        blob: Option<String>,
+    },
+}
+
+impl ToolContent {
+    /// Best-effort plain text rendering, for engines that only take strings.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Text { text } => text.clone(),
+            Self::Image { mime_type, .. } => format!("[image: {}]", mime_type),
+            Self::Resource {
+                text: Some(text), ..
+            } => text.clone(),
+            Self::Resource { uri, mime_type, .. } => match mime_type {
+                Some(mime_type) => format!("[resource: {} ({})]", uri, mime_type),
+                None => format!("[resource: {}]", uri),
+            },
+        }
+    }
+}
+
+impl From<RawContent> for ToolContent {
+    fn from(content: RawContent) -> Self {
+        match content {
+            RawContent::Text(t) => Self::Text { text: t.text },
+            RawContent::Image(i) => Self::Image {
+                data: i.data,
+                mime_type: i.mime_type,
+            },
+            RawContent::Resource(r) => match r.resource {
+                ResourceContents::TextResourceContents {
+                    uri,
+                    mime_type,
+                    text,
+                } => Self::Resource {
+                    uri,
+                    mime_type,
+                    text: Some(text),
+                    blob: None,
+                },
+                ResourceContents::BlobResourceContents {
+                    uri,
+                    mime_type,
+                    blob,
+                } => Self::Resource {
+                    uri,
+                    mime_type,
+                    text: None,
+                    blob: Some(blob),
+                },
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolResult {
+    pub content: Vec<ToolContent>,
+    pub is_error: bool,
+    /// Every part of `content` flattened into a single string.
+    pub text: String,
+}
+
+impl From<CallToolResult> for ToolResult {
+    fn from(result: CallToolResult) -> Self {
+        let content: Vec<ToolContent> = result
+            .content
+            .into_iter()
+            .map(|c| ToolContent::from(c.raw))
+            .collect();
+
+        let text = content
+            .iter()
+            .map(ToolContent::text)
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Self {
+            content,
+            is_error: result.is_error.unwrap_or(false),
+            text,
+        }
+    }
+}
+
+/// A prompt message in the shape of a session message, ready to be inserted as
+/// is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptMessage {
+    pub role: PromptMessageRole,
+    pub content: ToolContent,
+    /// `content` rendered as plain text.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptResult {
+    pub description: Option<String>,
+    pub messages: Vec<PromptMessage>,
+}
+
+impl From<GetPromptResult> for PromptResult {
+    fn from(result: GetPromptResult) -> Self {
+        let messages = result
+            .messages
+            .into_iter()
+            .map(|message| {
+                let content = ToolContent::from(match message.content {
+                    PromptMessageContent::Text { text } => RawContent::Text(RawTextContent { text }),
+                    PromptMessageContent::Image { image } => RawContent::Image(image.raw),
+                    PromptMessageContent::Resource { resource } => {
+                        RawContent::Resource(resource.raw)
+                    }
+                });
+
+                PromptMessage {
+                    role: message.role,
+                    text: content.text(),
+                    content,
+                }
+            })
+            .collect();
+
+        Self {
+            description: result.description,
+            messages,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! How running servers are doing. Each has a status, kept up to date by
+//! pinging it every so often and watching for it to exit. Every change of
+//! status is emitted as an `mcp/server-status` event.
+
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::server::McpServer;
+
+/// How often servers are pinged.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a server has to answer a ping before it's considered degraded.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Being started, or running but not yet attached to a session.
+    Starting,
+    Ready,
+    /// Still running, but not answering pings.
+    Degraded,
+    /// Exited, or stopped.
+    Dead,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusChanged {
+    pub status: Status,
+    pub previous: Status,
+    pub error: Option<String>,
+}
+
+/// A server as it's running in a session, for `list_running_servers`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningServer {
+    pub session_id: i32,
+    pub name: String,
+    pub status: Status,
+    /// Only local servers have one.
+    pub pid: Option<u32>,
+    pub uptime_ms: u64,
+    pub last_error: Option<String>,
+    pub tool_count: usize,
+}
+
+#[derive(Debug)]
+struct Inner {
+    status: Status,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Health(Arc<Mutex<Inner>>);
+
+impl Default for Health {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Inner {
+            status: Status::Starting,
+            error: None,
+        })))
+    }
+}
+
+impl Health {
+    pub fn status(&self) -> Status {
+        self.0
+            .lock()
+            .map(|inner| inner.status)
+            .unwrap_or(Status::Dead)
+    }
+
+    /// The last thing to go wrong, even if the server has since recovered.
+    pub fn last_error(&self) -> Option<String> {
+        self.0.lock().ok()?.error.clone()
+    }
+
+    /// Move to `status`, returning the change if it is one. The dead stay
+    /// dead.
+    pub fn set(&self, status: Status, error: Option<String>) -> Option<StatusChanged> {
+        let mut inner = self.0.lock().ok()?;
+
+        if error.is_some() {
+            inner.error = error.clone();
+        }
+
+        let previous = inner.status;
+        if previous == status || previous == Status::Dead {
+            return None;
+        }
+        inner.status = status;
+
+        Some(StatusChanged {
+            status,
+            previous,
+            error,
+        })
+    }
+}
+
+/// Keep an eye on `server` until it's stopped or exits. Only holds on to it
+/// while pinging, so it can still be dropped in the meantime.
+pub fn watch(server: &Arc<McpServer>) {
+    let weak = Arc::downgrade(server);
+    let stopping = server.stopping();
+    let exit = server.exit();
+
+    tauri::async_runtime::spawn(async move {
+        let exited = async {
+            match exit {
+                Some(mut exit) => exit
+                    .wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|exit| *exit),
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(exited);
+
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(PING_INTERVAL) => ping(&weak).await,
+                exit = &mut exited => {
+                    if let Some(server) = weak.upgrade() {
+                        let error = exit.map(|exit| exit.describe());
+                        server.set_status(Status::Dead, error);
+                    }
+                    return;
+                }
+                _ = stopping.cancelled() => {
+                    if let Some(server) = weak.upgrade() {
+                        server.set_status(Status::Dead, None);
+                    }
+                    return;
+                }
+            }
+        }
+    });
+}
+
+async fn ping(server: &Weak<McpServer>) {
+    let Some(server) = server.upgrade() else {
+        return;
+    };
+
+    match server.ping().await {
+        Ok(()) => server.set_status(Status::Ready, None),
+        Err(e) => server.set_status(Status::Degraded, Some(e.to_string())),
+    }
+}
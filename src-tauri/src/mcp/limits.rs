@@ -0,0 +1,370 @@
+//! Caps on what a local server can use. Memory goes in a cgroup of its own
+//! when we've been delegated one, so that the kernel tells us when it's hit;
+//! everything else, and memory when there's no cgroup to be had, is an rlimit
+//! set in the child between `fork` and `exec`.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tokio::process::Command;
+
+use super::config::ResourceLimits;
+use super::sandbox::check;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const MB: u64 = 1024 * 1024;
+
+/// The limit a server was killed for hitting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    Memory,
+    Cpu,
+}
+
+impl Limit {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Limit::Memory => "Killed for exceeding its memory limit.",
+            Limit::Cpu => "Killed for exceeding its CPU time limit.",
+        }
+    }
+}
+
+/// A cgroup holding a single server, and anything it spawns.
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Make a cgroup for a server, if cgroup v2 is mounted and the memory
+    /// controller has been delegated to us.
+    fn create(memory: u64) -> Result<Self> {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+
+        let parent = parent().map_err(|e| anyhow!("{}", e))?;
+        let path = parent.join(format!(
+            "tome-mcp-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::create_dir(&path)?;
+
+        if let Err(e) = std::fs::write(path.join("memory.max"), memory.to_string()) {
+            let _ = std::fs::remove_dir(&path);
+            return Err(e.into());
+        }
+
+        // Otherwise the limit is only a suggestion to start swapping.
+        let _ = std::fs::write(path.join("memory.swap.max"), "0");
+
+        Ok(Self { path })
+    }
+
+    /// Whether anything in the cgroup has been killed for running out of
+    /// memory.
+    pub fn oom_killed(&self) -> bool {
+        let Ok(events) = std::fs::read_to_string(self.path.join("memory.events")) else {
+            return false;
+        };
+
+        events
+            .lines()
+            .filter_map(|line| line.strip_prefix("oom_kill "))
+            .any(|count| count.trim().parse::<u64>().is_ok_and(|count| count > 0))
+    }
+
+    /// Kill whatever's left in the cgroup, and remove it.
+    pub async fn remove(self) {
+        let _ = std::fs::write(self.path.join("cgroup.kill"), "1");
+
+        // Killed processes take a moment to leave.
+        for _ in 0..20 {
+            if std::fs::remove_dir(&self.path).is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        log::warn!("Failed to remove cgroup {}", self.path.display());
+    }
+}
+
+/// Where we moved ourselves to, if we had to, for `restore` to undo.
+static LEAF: OnceLock<PathBuf> = OnceLock::new();
+
+/// Where servers' cgroups go: the one we were started in, once we've moved
+/// out of its way. Cgroup v2 only lets a cgroup hand controllers down to its
+/// children once it has no processes of its own, which ours, on the desktop,
+/// usually does. So we move the whole app into a leaf of its own,
+/// `tome-<pid>`, until `restore` moves it back on exit.
+fn parent() -> Result<&'static Path, &'static str> {
+    static PARENT: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+    PARENT
+        .get_or_init(|| delegate().map_err(|e| e.to_string()))
+        .as_deref()
+        .map_err(String::as_str)
+}
+
+fn delegate() -> Result<PathBuf> {
+    let own = std::fs::read_to_string("/proc/self/cgroup")?;
+    let own = own
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| anyhow!("cgroup v2 isn't mounted"))?;
+    let parent = Path::new(CGROUP_ROOT).join(own.trim_start_matches('/'));
+
+    // Left behind by earlier runs, by servers still exiting when we did, or
+    // by us if we never got to `restore`.
+    for entry in std::fs::read_dir(&parent)?.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let owner = name
+            .strip_prefix("tome-mcp-")
+            .and_then(|rest| rest.split('-').next())
+            .or_else(|| name.strip_prefix("tome-"))
+            .and_then(|pid| pid.parse::<u32>().ok());
+
+        if owner.is_some_and(|pid| !Path::new(&format!("/proc/{}", pid)).exists()) {
+            let _ = std::fs::remove_dir(entry.path());
+        }
+    }
+
+    let subtree = parent.join("cgroup.subtree_control");
+    if std::fs::read_to_string(&subtree)?
+        .split_whitespace()
+        .any(|c| c == "memory")
+    {
+        return Ok(parent);
+    }
+
+    let leaf = parent.join(format!("tome-{}", std::process::id()));
+    if let Err(e) = std::fs::create_dir(&leaf) {
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(e.into());
+        }
+    }
+    std::fs::write(leaf.join("cgroup.procs"), std::process::id().to_string())?;
+    let _ = LEAF.set(leaf);
+
+    std::fs::write(&subtree, "+memory").map_err(|e| {
+        anyhow!(
+            "Failed to enable the memory controller in {}, is anything else running in it? {}",
+            parent.display(),
+            e
+        )
+    })?;
+
+    Ok(parent)
+}
+
+/// Move the app back into the cgroup it was started in, and remove the leaf
+/// `parent` moved it into. For when we're exiting, and servers are done with
+/// the memory controller.
+pub fn restore() {
+    let Some(leaf) = LEAF.get() else {
+        return;
+    };
+    let Some(parent) = leaf.parent() else {
+        return;
+    };
+
+    // The cgroup can't take processes back while it hands controllers down.
+    let _ = std::fs::write(parent.join("cgroup.subtree_control"), "-memory");
+
+    let moved = std::fs::write(parent.join("cgroup.procs"), std::process::id().to_string())
+        .and_then(|()| std::fs::remove_dir(leaf));
+    if let Err(e) = moved {
+        log::warn!("Failed to remove cgroup {}: {}", leaf.display(), e);
+    }
+}
+
+/// Catches cgroups made for servers that then failed to start, which are
+/// still empty.
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir(&self.path);
+    }
+}
+
+/// Run `cmd` under `limits`, returning the cgroup it'll be placed in, if
+/// any.
+pub fn apply(cmd: &mut Command, limits: &ResourceLimits) -> Result<Option<Cgroup>> {
+    let cgroup = limits.memory_mb.and_then(|mb| {
+        Cgroup::create(mb.saturating_mul(MB))
+            .map_err(|e| {
+                log::warn!(
+                    "No cgroup for memory limit, falling back to RLIMIT_DATA, which can't tell \
+                     running out of memory from a crash: {}",
+                    e
+                )
+            })
+            .ok()
+    });
+
+    // Opened up front, so the child only has to write to it.
+    let procs = match &cgroup {
+        Some(cgroup) => Some(
+            File::options()
+                .write(true)
+                .open(cgroup.path.join("cgroup.procs"))?,
+        ),
+        None => None,
+    };
+
+    let memory = match cgroup {
+        Some(_) => None,
+        None => limits.memory_mb.map(|mb| mb.saturating_mul(MB)),
+    };
+    let cpu = limits.cpu_seconds;
+    let files = limits.open_files;
+
+    // SAFETY: runs in the child between fork and exec, where nothing may
+    // allocate or take a lock. It only writes to the cgroup's `cgroup.procs`,
+    // opened above, and calls setrlimit with limits worked out above.
+    unsafe {
+        cmd.pre_exec(move || {
+            // Writing 0 moves the writer.
+            if let Some(mut procs) = procs.as_ref() {
+                procs.write_all(b"0")?;
+            }
+
+            if let Some(bytes) = memory {
+                check(libc::setrlimit(libc::RLIMIT_DATA, &rlimit(bytes, bytes)))?;
+            }
+
+            // A second to spare, so that the process is sent SIGXCPU before
+            // it's sent SIGKILL, and we can tell why it died.
+            if let Some(seconds) = cpu {
+                check(libc::setrlimit(
+                    libc::RLIMIT_CPU,
+                    &rlimit(seconds, seconds.saturating_add(1)),
+                ))?;
+            }
+
+            if let Some(files) = files {
+                check(libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit(files, files)))?;
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(cgroup)
+}
+
+/// Wait for process `pid` to exit, and return the CPU time it used. Leaves it
+/// to be reaped by whoever's waiting on it. Blocks.
+pub fn cpu_time_at_exit(pid: u32) -> Option<Duration> {
+    // SAFETY: waitid only writes to `info` and `usage`, and WNOWAIT leaves the
+    // process as it found it. The raw system call is the only way to get at
+    // the usage.
+    unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        let mut usage: libc::rusage = std::mem::zeroed();
+        loop {
+            let result = libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT,
+                &mut usage as *mut libc::rusage,
+            );
+            if result == 0 {
+                break;
+            }
+            if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                return None;
+            }
+        }
+
+        let time = |time: libc::timeval| {
+            Duration::from_secs(time.tv_sec.max(0) as u64)
+                + Duration::from_micros(time.tv_usec.max(0) as u64)
+        };
+        Some(time(usage.ru_utime) + time(usage.ru_stime))
+    }
+}
+
+/// Work out whether the server exited because of one of its limits. `cpu` is
+/// the CPU time it used, if known.
+pub fn exceeded(
+    limits: &ResourceLimits,
+    cgroup: Option<&Cgroup>,
+    signal: Option<i32>,
+    cpu: Option<Duration>,
+) -> Option<Limit> {
+    if let Some(seconds) = limits.cpu_seconds {
+        match signal {
+            Some(libc::SIGXCPU) => return Some(Limit::Cpu),
+            // Servers that handle SIGXCPU carry on until the hard limit.
+            Some(libc::SIGKILL) if cpu.is_some_and(|cpu| cpu >= Duration::from_secs(seconds)) => {
+                return Some(Limit::Cpu)
+            }
+            _ => {}
+        }
+    }
+
+    // Without a cgroup, running out of memory looks like any other crash.
+    if cgroup.is_some_and(Cgroup::oom_killed) {
+        return Some(Limit::Memory);
+    }
+
+    None
+}
+
+/// Describe the limits, for the server's log.
+pub fn describe(limits: &ResourceLimits, cgroup: Option<&Cgroup>) -> String {
+    let mut parts = vec![];
+    if let Some(mb) = limits.memory_mb {
+        let how = match cgroup {
+            Some(_) => "cgroup",
+            None => "rlimit",
+        };
+        parts.push(format!("memory {} MB ({})", mb, how));
+    }
+    if let Some(seconds) = limits.cpu_seconds {
+        parts.push(format!("CPU time {}s", seconds));
+    }
+    if let Some(files) = limits.open_files {
+        parts.push(format!("{} open files", files));
+    }
+    parts.join(", ")
+}
+
+/// Arguments passing `limits` on to a container runtime.
+pub fn container_args(limits: &ResourceLimits) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(mb) = limits.memory_mb {
+        args.extend(["--memory".to_string(), format!("{}m", mb)]);
+    }
+    if let Some(seconds) = limits.cpu_seconds {
+        args.extend([
+            "--ulimit".to_string(),
+            format!("cpu={}:{}", seconds, seconds.saturating_add(1)),
+        ]);
+    }
+    if let Some(files) = limits.open_files {
+        args.extend([
+            "--ulimit".to_string(),
+            format!("nofile={}:{}", files, files),
+        ]);
+    }
+    args
+}
+
+fn rlimit(soft: u64, hard: u64) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    }
+}
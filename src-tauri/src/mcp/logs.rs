@@ -0,0 +1,174 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::Emitter;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::Notify;
+
+use crate::APP_HANDLE;
+
+/// How many lines of output we hold onto per server.
+const MAX_LINES: usize = 1000;
+
+/// How many lines we tack onto startup errors.
+const ERROR_LINES: usize = 20;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogEvent {
+    session_id: Option<i32>,
+    server: Option<String>,
+    pid: Option<u32>,
+    line: String,
+}
+
+#[derive(Debug, Default)]
+struct Source {
+    /// The sessions the server is attached to, and its name in each.
+    sessions: HashMap<i32, String>,
+    pid: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    lines: Mutex<VecDeque<String>>,
+    source: Mutex<Source>,
+    eof: AtomicBool,
+    closed: Notify,
+}
+
+/// Bounded buffer of a server's stderr, shared between the task reading the
+/// pipe and the `McpServer` that owns the process.
+#[derive(Debug, Clone, Default)]
+pub struct ServerLogs {
+    inner: Arc<Inner>,
+}
+
+impl ServerLogs {
+    /// Consume `reader` line by line until EOF, buffering each line and
+    /// emitting it as an `mcp/log` event.
+    pub fn capture<R: AsyncRead + Unpin + Send + 'static>(&self, reader: R, pid: Option<u32>) {
+        if let Ok(mut source) = self.inner.source.lock() {
+            source.pid = pid;
+        }
+
+        let logs = self.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => logs.push(line),
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::warn!("Failed to read MCP server output: {}", e);
+                        break;
+                    }
+                }
+            }
+            logs.inner.eof.store(true, Ordering::SeqCst);
+            logs.inner.closed.notify_waiters();
+        });
+    }
+
+    /// Tag future events with the session and name the server is running as.
+    pub fn attach(&self, session_id: i32, server: &str) {
+        if let Ok(mut source) = self.inner.source.lock() {
+            source.sessions.insert(session_id, server.to_string());
+        }
+    }
+
+    pub fn detach(&self, session_id: i32) {
+        if let Ok(mut source) = self.inner.source.lock() {
+            source.sessions.remove(&session_id);
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.inner
+            .lines
+            .lock()
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines();
+        lines[lines.len().saturating_sub(n)..].to_vec()
+    }
+
+    /// Resolves once the pipe hits EOF, ie. the process has gone away.
+    pub async fn closed(&self) {
+        let closed = self.inner.closed.notified();
+        if self.inner.eof.load(Ordering::SeqCst) {
+            return;
+        }
+        closed.await;
+    }
+
+    /// Decorate a startup error with whatever the server printed before it
+    /// died, which is almost always more useful than the error itself.
+    pub async fn annotate(&self, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        let error = error.into();
+
+        // Give the reader a moment to drain the pipe of a process that just
+        // exited.
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(500), self.closed()).await;
+
+        let tail = self.tail(ERROR_LINES);
+        if tail.is_empty() {
+            error
+        } else {
+            anyhow::anyhow!("{}\n\nServer output:\n{}", error, tail.join("\n"))
+        }
+    }
+
+    /// Add a line of our own, about the server rather than from it.
+    pub fn note(&self, line: &str) {
+        self.push(format!("[tome] {}", line));
+    }
+
+    fn push(&self, line: String) {
+        if let Ok(mut lines) = self.inner.lines.lock() {
+            if lines.len() >= MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.clone());
+        }
+
+        self.emit(line);
+    }
+
+    fn emit(&self, line: String) {
+        let Some(app) = APP_HANDLE.get() else {
+            return;
+        };
+
+        let events: Vec<LogEvent> = match self.inner.source.lock() {
+            Ok(source) if source.sessions.is_empty() => vec![LogEvent {
+                session_id: None,
+                server: None,
+                pid: source.pid,
+                line,
+            }],
+            Ok(source) => source
+                .sessions
+                .iter()
+                .map(|(session_id, server)| LogEvent {
+                    session_id: Some(*session_id),
+                    server: Some(server.clone()),
+                    pid: source.pid,
+                    line: line.clone(),
+                })
+                .collect(),
+            Err(_) => return,
+        };
+
+        for event in events {
+            if let Err(e) = app.emit("mcp/log", event) {
+                log::error!("Failed to emit mcp/log event: {}", e);
+            }
+        }
+    }
+}
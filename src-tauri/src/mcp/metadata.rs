@@ -0,0 +1,89 @@
+//! What a server says about itself when it starts, and the tools it offers.
+//!
+//! Finding out means starting the server, which for most means downloading
+//! it first, so what we learn is kept until it's invalidated.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rmcp::model::{InitializeResult, Tool};
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use super::config::McpServerConfig;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    #[serde(flatten)]
+    pub peer_info: InitializeResult,
+    pub tools: Vec<Tool>,
+}
+
+/// One cell per server, so that everyone asking about a server nobody knows
+/// about yet waits on the same start, rather than starting it again.
+#[derive(Debug, Default)]
+pub struct MetadataCache(Mutex<HashMap<u64, Arc<OnceCell<Metadata>>>>);
+
+/// Identifies a server by what it runs and how, or where it's found and as
+/// whom.
+pub fn key(config: &McpServerConfig) -> u64 {
+    let env: BTreeMap<&String, &String> = config.env.iter().collect();
+    let headers: BTreeMap<&String, &String> = config.headers.iter().collect();
+
+    let mut hasher = DefaultHasher::new();
+    config.transport.hash(&mut hasher);
+    config.command.hash(&mut hasher);
+    config.args.hash(&mut hasher);
+    config.cwd.hash(&mut hasher);
+    config.container.hash(&mut hasher);
+    config.sandbox.hash(&mut hasher);
+    config.limits.hash(&mut hasher);
+    env.hash(&mut hasher);
+    config.url.hash(&mut hasher);
+    headers.hash(&mut hasher);
+    config.bearer_token.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl MetadataCache {
+    /// What we know about the server `config` runs, finding out with `fetch`
+    /// if nobody has yet.
+    pub async fn get_or_fetch<F, Fut>(&self, config: &McpServerConfig, fetch: F) -> Result<Metadata>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Metadata>>,
+    {
+        let cell = match self.0.lock() {
+            Ok(mut cache) => cache.entry(key(config)).or_default().clone(),
+            Err(_) => Default::default(),
+        };
+
+        cell.get_or_try_init(fetch).await.cloned()
+    }
+
+    pub fn insert(&self, config: &McpServerConfig, metadata: Metadata) {
+        if let Ok(mut cache) = self.0.lock() {
+            cache.insert(key(config), Arc::new(OnceCell::new_with(Some(metadata))));
+        }
+    }
+
+    /// Forget what we know about the server `config` runs, or about every
+    /// server if there's no `config`.
+    pub fn invalidate(&self, config: Option<&McpServerConfig>) {
+        let Ok(mut cache) = self.0.lock() else {
+            return;
+        };
+
+        match config {
+            Some(config) => {
+                cache.remove(&key(config));
+            }
+            None => cache.clear(),
+        }
+    }
+}
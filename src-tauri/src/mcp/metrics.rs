@@ -0,0 +1,323 @@
+//! Call counts and latencies of every server's tools, kept for as long as the
+//! app runs. Servers are told apart by version as well as name, so that an
+//! upgrade can be compared with what came before it.
+//!
+//! Besides `get_mcp_metrics`, they can be scraped by Prometheus from
+//! `http://localhost:<port>/metrics`, once the endpoint's been switched on.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use super::content::ToolResult;
+use super::server::CallError;
+
+/// Upper bounds of the latency histogram's buckets, in seconds. Prometheus'
+/// defaults, stretched out to cover the slow tools some servers have.
+const BUCKETS: [f64; 15] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// The most of a scrape request we'll read, headers and all.
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// How a call ended.
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    Ok,
+    /// The call failed, or the tool said it did.
+    Error,
+    Timeout,
+    Cancelled,
+}
+
+impl Outcome {
+    pub fn of(result: &Result<ToolResult>) -> Self {
+        match result {
+            Ok(result) if !result.is_error => Outcome::Ok,
+            Ok(_) => Outcome::Error,
+            Err(e) => match e.downcast_ref::<CallError>() {
+                Some(CallError::Timeout(_)) => Outcome::Timeout,
+                Some(CallError::Cancelled) => Outcome::Cancelled,
+                None => Outcome::Error,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    server: String,
+    version: String,
+    tool: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    ok: u64,
+    errors: u64,
+    timeouts: u64,
+    cancelled: u64,
+    /// Calls per bucket, plus one for calls slower than the last bound.
+    buckets: [u64; BUCKETS.len() + 1],
+    total: Duration,
+}
+
+impl Counts {
+    fn calls(&self) -> u64 {
+        self.ok + self.errors + self.timeouts + self.cancelled
+    }
+
+    /// Calls per bucket, counting those in lower buckets too, as Prometheus
+    /// has it.
+    fn cumulative(&self) -> impl Iterator<Item = u64> + '_ {
+        self.buckets.iter().scan(0, |sum, count| {
+            *sum += count;
+            Some(*sum)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bucket {
+    /// Upper bound in milliseconds, or `None` for the catch-all.
+    pub le_ms: Option<f64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolMetrics {
+    pub server: String,
+    pub version: String,
+    pub tool: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub timeouts: u64,
+    pub cancelled: u64,
+    pub mean_ms: f64,
+    /// Cumulative, as in Prometheus.
+    pub buckets: Vec<Bucket>,
+}
+
+/// Metrics of every tool called so far. Cheap to clone, and every clone
+/// shares the same numbers.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Mutex<BTreeMap<Key, Counts>>>);
+
+impl Metrics {
+    pub fn record(
+        &self,
+        server: &str,
+        version: &str,
+        tool: &str,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        let Ok(mut metrics) = self.0.lock() else {
+            return;
+        };
+
+        let key = Key {
+            server: server.to_string(),
+            version: version.to_string(),
+            tool: tool.to_string(),
+        };
+        let counts = metrics.entry(key).or_default();
+
+        match outcome {
+            Outcome::Ok => counts.ok += 1,
+            Outcome::Error => counts.errors += 1,
+            Outcome::Timeout => counts.timeouts += 1,
+            Outcome::Cancelled => counts.cancelled += 1,
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(BUCKETS.len());
+        counts.buckets[bucket] += 1;
+        counts.total += elapsed;
+    }
+
+    pub fn snapshot(&self) -> Vec<ToolMetrics> {
+        let Ok(metrics) = self.0.lock() else {
+            return vec![];
+        };
+
+        metrics
+            .iter()
+            .map(|(key, counts)| {
+                let bounds = BUCKETS.iter().map(|le| Some(le * 1000.0)).chain([None]);
+
+                ToolMetrics {
+                    server: key.server.clone(),
+                    version: key.version.clone(),
+                    tool: key.tool.clone(),
+                    calls: counts.calls(),
+                    errors: counts.errors,
+                    timeouts: counts.timeouts,
+                    cancelled: counts.cancelled,
+                    mean_ms: counts.total.as_secs_f64() * 1000.0 / counts.calls().max(1) as f64,
+                    buckets: bounds
+                        .zip(counts.cumulative())
+                        .map(|(le_ms, count)| Bucket { le_ms, count })
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Everything, in Prometheus' text exposition format.
+    pub fn prometheus(&self) -> String {
+        let Ok(metrics) = self.0.lock() else {
+            return String::new();
+        };
+
+        let mut out = String::new();
+
+        out.push_str("# HELP tome_mcp_tool_calls_total MCP tool calls, by how they ended.\n");
+        out.push_str("# TYPE tome_mcp_tool_calls_total counter\n");
+        for (key, counts) in metrics.iter() {
+            let outcomes = [
+                ("ok", counts.ok),
+                ("error", counts.errors),
+                ("timeout", counts.timeouts),
+                ("cancelled", counts.cancelled),
+            ];
+            for (outcome, count) in outcomes {
+                let _ = writeln!(
+                    out,
+                    "tome_mcp_tool_calls_total{{{},outcome=\"{}\"}} {}",
+                    labels(key),
+                    outcome,
+                    count
+                );
+            }
+        }
+
+        out.push_str("# HELP tome_mcp_tool_call_duration_seconds How long MCP tool calls take.\n");
+        out.push_str("# TYPE tome_mcp_tool_call_duration_seconds histogram\n");
+        for (key, counts) in metrics.iter() {
+            let labels = labels(key);
+            let bounds = BUCKETS
+                .iter()
+                .map(|le| le.to_string())
+                .chain(["+Inf".to_string()]);
+            for (le, count) in bounds.zip(counts.cumulative()) {
+                let _ = writeln!(
+                    out,
+                    "tome_mcp_tool_call_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "tome_mcp_tool_call_duration_seconds_sum{{{}}} {}",
+                labels,
+                counts.total.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "tome_mcp_tool_call_duration_seconds_count{{{}}} {}",
+                labels,
+                counts.calls()
+            );
+        }
+
+        out
+    }
+}
+
+fn labels(key: &Key) -> String {
+    format!(
+        "server=\"{}\",version=\"{}\",tool=\"{}\"",
+        escape(&key.server),
+        escape(&key.version),
+        escape(&key.tool)
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serve `metrics` on `localhost:port` until `ct` is cancelled. Only binds to
+/// the loopback interface, as nothing else has any business scraping them.
+pub async fn serve(metrics: Metrics, port: u16, ct: CancellationToken) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    log::info!("Serving MCP metrics on http://localhost:{}/metrics", port);
+
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log::warn!("Failed to accept metrics connection: {}", e);
+                        continue;
+                    }
+                },
+                _ = ct.cancelled() => break,
+            };
+
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream, &metrics).await {
+                    log::debug!("Failed to answer metrics request: {}", e);
+                }
+            });
+        }
+
+        log::info!("Stopped serving MCP metrics on port {}", port);
+    });
+
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+
+    let (status, body) = match (line.next(), line.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.prometheus()),
+        _ => ("404 Not Found", String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! Servers run in process groups of their own, so that everything they spawn
+//! can be signalled at once, even after it's been re-parented. The groups are
+//! written down as they come and go, so that if we crash, the next run can
+//! find any servers we left behind and kill them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::process::Container;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Group {
+    pgid: u32,
+    /// When the group's leader started, in clock ticks since boot, to tell it
+    /// apart from a later process that happens to get the same pid.
+    started: u64,
+    /// Containers aren't in the group, only their runtime's client is.
+    container: Option<Container>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    /// Pids are only meaningful until the next reboot.
+    boot_id: String,
+    groups: Vec<Group>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    record: Mutex<Record>,
+}
+
+/// The process groups of the servers we're running. Cheap to clone, and every
+/// clone writes to the same file.
+#[derive(Debug, Clone)]
+pub struct Groups(Arc<Inner>);
+
+impl Groups {
+    pub fn new(path: PathBuf) -> Self {
+        Self(Arc::new(Inner {
+            path,
+            record: Mutex::new(Record {
+                boot_id: boot_id().unwrap_or_default(),
+                groups: vec![],
+            }),
+        }))
+    }
+
+    /// Note the group led by `pgid`, along with the container it's running,
+    /// if any.
+    pub fn add(&self, pgid: u32, container: Option<&Container>) {
+        let Some(started) = started(pgid) else {
+            return;
+        };
+
+        self.update(|record| {
+            record.groups.push(Group {
+                pgid,
+                started,
+                container: container.cloned(),
+            })
+        });
+    }
+
+    /// Forget the group led by `pgid`, once there's nothing left of it.
+    pub fn remove(&self, pgid: u32) {
+        self.update(|record| record.groups.retain(|group| group.pgid != pgid));
+    }
+
+    /// Kill every group we're still running, along with its container. For
+    /// when we're exiting, and servers that haven't by now won't get another
+    /// chance. Groups whose container couldn't be removed are kept on record,
+    /// for the next run to reap.
+    pub fn kill_all(&self) {
+        let Ok(mut record) = self.0.record.lock() else {
+            return;
+        };
+
+        record.groups.retain(|group| !kill(group, "on exit"));
+
+        let saved = if record.groups.is_empty() {
+            fs::remove_file(&self.0.path).map_err(Into::into)
+        } else {
+            save(&self.0.path, &record)
+        };
+
+        if let Err(e) = saved {
+            log::warn!("Failed to record MCP server process groups: {}", e);
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Record)) {
+        let Ok(mut record) = self.0.record.lock() else {
+            return;
+        };
+
+        f(&mut record);
+
+        if let Err(e) = save(&self.0.path, &record) {
+            log::warn!("Failed to record MCP server process groups: {}", e);
+        }
+    }
+}
+
+fn save(path: &Path, record: &Record) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_vec(record)?)?;
+    Ok(())
+}
+
+/// Kill whatever's left of servers started by a previous run, as recorded in
+/// `path`. Only to be called before we've started any of our own.
+pub fn reap(path: &Path) {
+    let Ok(data) = fs::read(path) else {
+        return;
+    };
+
+    let record: Record = match serde_json::from_slice(&data) {
+        Ok(record) => record,
+        Err(e) => {
+            log::warn!("Ignoring unreadable MCP server process groups: {}", e);
+            return;
+        }
+    };
+
+    // After a reboot, there's nothing left to kill, and the pids belong to
+    // someone else.
+    if boot_id().is_none_or(|boot_id| boot_id != record.boot_id) {
+        return;
+    }
+
+    for group in &record.groups {
+        kill(group, "left behind by a previous run");
+    }
+
+    // So they're not reaped again, should we crash before starting a server.
+    let _ = fs::remove_file(path);
+}
+
+/// Kill what's left of `group`, and remove its container, returning whether
+/// the container's gone. `when` is for the log.
+fn kill(group: &Group, when: &str) -> bool {
+    // A leader that's still around must be the one we started. One that's
+    // gone may have left the rest of its group behind, and while they're
+    // around, its pid can't be taken by anyone else.
+    let ours = started(group.pgid).is_none_or(|started| started == group.started);
+
+    // SAFETY: killpg only sends a signal, and the group was checked to be ours
+    // first.
+    if ours && unsafe { libc::killpg(group.pgid as i32, libc::SIGKILL) } == 0 {
+        log::warn!("Killed MCP server process group {} {}", group.pgid, when);
+    }
+
+    // Containers outlive their runtime's client, and have names of their own.
+    let Some(container) = &group.container else {
+        return true;
+    };
+
+    log::warn!(
+        "Removing MCP server container {} {}",
+        container.name(),
+        when
+    );
+    container.remove()
+}
+
+fn boot_id() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .map(|id| id.trim().to_string())
+}
+
+/// When process `pid` started, in clock ticks since boot.
+fn started(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // The command name comes second, in parentheses, and may contain spaces,
+    // so fields are counted from after it. The start time is the 22nd.
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
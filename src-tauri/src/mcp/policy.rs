@@ -0,0 +1,344 @@
+//! Rules deciding which tool calls go ahead. Rules are checked in order, and
+//! the first to match a call decides it; calls no rule matches are allowed.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use super::annotations::ToolAnnotations;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Allow,
+    Deny,
+    /// Hold the call until the user approves it.
+    Ask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyRule {
+    /// Glob on the name the server runs under, ie. `github` or `*`.
+    #[serde(default = "any")]
+    pub server: String,
+    /// Glob on the tool's own name, without the server's.
+    #[serde(default = "any")]
+    pub tool: String,
+    /// Match on the tool's `destructiveHint`. Tools that don't say are taken
+    /// to be destructive, as the spec has it.
+    pub destructive: Option<bool>,
+    /// Match on the tool's `readOnlyHint`. Tools that don't say are taken not
+    /// to be.
+    pub read_only: Option<bool>,
+    /// Regexes that arguments, by name, must match. String arguments are
+    /// matched as they are, anything else as JSON.
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+    pub action: Action,
+}
+
+fn any() -> String {
+    "*".to_string()
+}
+
+/// Ways a call can be stopped by the policy.
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("PolicyError: calls to '{0}' are not allowed")]
+    Denied(String),
+
+    #[error("PolicyError: call to '{0}' was rejected by the user")]
+    Rejected(String),
+}
+
+#[derive(Debug)]
+struct Rule {
+    server: Regex,
+    tool: Regex,
+    destructive: Option<bool>,
+    read_only: Option<bool>,
+    arguments: Vec<(String, Regex)>,
+    action: Action,
+}
+
+impl TryFrom<PolicyRule> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(rule: PolicyRule) -> Result<Self> {
+        let arguments = rule
+            .arguments
+            .into_iter()
+            .map(|(name, pattern)| {
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| anyhow!("Invalid pattern for argument '{}': {}", name, e))?;
+                Ok((name, regex))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            server: glob(&rule.server)?,
+            tool: glob(&rule.tool)?,
+            destructive: rule.destructive,
+            read_only: rule.read_only,
+            arguments,
+            action: rule.action,
+        })
+    }
+}
+
+impl Rule {
+    fn matches(
+        &self,
+        server: &str,
+        tool: &str,
+        annotations: &ToolAnnotations,
+        arguments: &Map<String, Value>,
+    ) -> bool {
+        let destructive = annotations.destructive_hint.unwrap_or(true);
+        let read_only = annotations.read_only_hint.unwrap_or(false);
+
+        self.server.is_match(server)
+            && self.tool.is_match(tool)
+            && self.destructive.is_none_or(|d| d == destructive)
+            && self.read_only.is_none_or(|r| r == read_only)
+            && self
+                .arguments
+                .iter()
+                .all(|(name, pattern)| match arguments.get(name) {
+                    Some(Value::String(value)) => pattern.is_match(value),
+                    Some(value) => pattern.is_match(&value.to_string()),
+                    None => false,
+                })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    pub fn new(rules: Vec<PolicyRule>) -> Result<Self> {
+        Ok(Self {
+            rules: rules
+                .into_iter()
+                .map(Rule::try_from)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Decide what to do with a call to `tool` on `server`.
+    pub fn check(
+        &self,
+        server: &str,
+        tool: &str,
+        annotations: &ToolAnnotations,
+        arguments: &Map<String, Value>,
+    ) -> Action {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(server, tool, annotations, arguments))
+            .map(|rule| rule.action)
+            .unwrap_or(Action::Allow)
+    }
+}
+
+/// Compile a glob, where `*` matches anything and `?` any one character, into
+/// a regex matching whole names.
+fn glob(pattern: &str) -> Result<Regex> {
+    let regex = regex::escape(pattern)
+        .replace(r"\*", ".*")
+        .replace(r"\?", ".");
+
+    Regex::new(&format!("^{}$", regex)).map_err(|e| anyhow!("Invalid glob '{}': {}", pattern, e))
+}
+
+/// A call held for the user's approval, as handed to the frontend.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolApproval {
+    pub id: String,
+    pub session_id: i32,
+    pub server: String,
+    pub tool: String,
+    pub arguments: Map<String, Value>,
+    pub annotations: ToolAnnotations,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ToolApprovalCancelled {
+    pub id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rule(server: &str, tool: &str, action: Action) -> PolicyRule {
+        PolicyRule {
+            server: server.to_string(),
+            tool: tool.to_string(),
+            destructive: None,
+            read_only: None,
+            arguments: HashMap::new(),
+            action,
+        }
+    }
+
+    fn arguments(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let policy = Policy::new(vec![
+            rule("github", "delete_*", Action::Deny),
+            rule("github", "*", Action::Ask),
+            rule("*", "*", Action::Deny),
+        ])
+        .unwrap();
+        let none = Map::new();
+        let annotations = ToolAnnotations::default();
+
+        assert_eq!(
+            policy.check("github", "delete_repo", &annotations, &none),
+            Action::Deny
+        );
+        assert_eq!(
+            policy.check("github", "list_repos", &annotations, &none),
+            Action::Ask
+        );
+        assert_eq!(
+            policy.check("fetch", "fetch", &annotations, &none),
+            Action::Deny
+        );
+    }
+
+    #[test]
+    fn unmatched_calls_are_allowed() {
+        let policy = Policy::new(vec![rule("github", "*", Action::Deny)]).unwrap();
+        let annotations = ToolAnnotations::default();
+
+        assert_eq!(
+            policy.check("fetch", "fetch", &annotations, &Map::new()),
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        let policy = Policy::new(vec![
+            rule("git?ub", "get_*", Action::Deny),
+            rule("*", "a.c", Action::Deny),
+        ])
+        .unwrap();
+        let none = Map::new();
+        let annotations = ToolAnnotations::default();
+
+        assert_eq!(
+            policy.check("github", "get_issue", &annotations, &none),
+            Action::Deny
+        );
+        assert_eq!(
+            policy.check("github", "forget_issue", &annotations, &none),
+            Action::Allow
+        );
+        assert_eq!(
+            policy.check("my-github", "get_issue", &annotations, &none),
+            Action::Allow
+        );
+        assert_eq!(
+            policy.check("githubs", "get_issue", &annotations, &none),
+            Action::Allow
+        );
+        assert_eq!(
+            policy.check("gitub", "get_issue", &annotations, &none),
+            Action::Allow
+        );
+
+        // Anything other than `*` and `?` is taken literally.
+        assert_eq!(policy.check("fs", "a.c", &annotations, &none), Action::Deny);
+        assert_eq!(
+            policy.check("fs", "abc", &annotations, &none),
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn arguments_match_as_strings_or_json() {
+        let mut deny = rule("*", "*", Action::Deny);
+        deny.arguments = HashMap::from([
+            ("path".to_string(), "^/etc/".to_string()),
+            ("recursive".to_string(), "^true$".to_string()),
+        ]);
+        let mut ask = rule("*", "*", Action::Ask);
+        ask.arguments = HashMap::from([("options".to_string(), r#""force":true"#.to_string())]);
+        let policy = Policy::new(vec![deny, ask]).unwrap();
+        let annotations = ToolAnnotations::default();
+
+        let check = |value| policy.check("fs", "remove", &annotations, &arguments(value));
+
+        assert_eq!(
+            check(json!({ "path": "/etc/passwd", "recursive": true })),
+            Action::Deny
+        );
+        assert_eq!(
+            check(json!({ "path": "/etc/passwd", "recursive": false })),
+            Action::Allow
+        );
+        assert_eq!(check(json!({ "path": "/etc/passwd" })), Action::Allow);
+        assert_eq!(
+            check(json!({ "path": "/tmp/etc/", "recursive": true })),
+            Action::Allow
+        );
+        assert_eq!(check(json!({ "options": { "force": true } })), Action::Ask);
+        assert_eq!(
+            check(json!({ "options": { "force": false } })),
+            Action::Allow
+        );
+    }
+
+    #[test]
+    fn missing_annotations_are_destructive_and_not_read_only() {
+        let mut destructive = rule("*", "*", Action::Ask);
+        destructive.destructive = Some(true);
+        let mut read_only = rule("*", "*", Action::Allow);
+        read_only.read_only = Some(true);
+
+        let policy = Policy::new(vec![read_only, destructive]).unwrap();
+        let none = Map::new();
+
+        let unannotated = ToolAnnotations::default();
+        assert_eq!(
+            policy.check("fs", "write", &unannotated, &none),
+            Action::Ask
+        );
+
+        let safe = ToolAnnotations {
+            read_only_hint: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(policy.check("fs", "read", &safe, &none), Action::Allow);
+
+        let harmless = ToolAnnotations {
+            destructive_hint: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(policy.check("fs", "touch", &harmless, &none), Action::Allow);
+    }
+
+    #[test]
+    fn invalid_patterns_are_refused() {
+        let mut bad = rule("*", "*", Action::Deny);
+        bad.arguments = HashMap::from([("path".to_string(), "(".to_string())]);
+
+        assert!(Policy::new(vec![bad]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use rmcp::model::Root;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Mutex;
+
+use super::config::McpServerConfig;
+use super::health::{self, Status};
+use super::server::McpServer;
+use crate::state::State;
+
+#[derive(Debug, Default)]
+struct Slot {
+    server: Option<Arc<McpServer>>,
+    sessions: HashSet<i32>,
+}
+
+/// Local servers shared between sessions. Sessions that launch the same
+/// command, with the same arguments and environment, get the same process,
+/// which is only shut down once the last of them lets go of it.
+#[derive(Debug, Default)]
+pub struct Pool {
+    slots: Mutex<HashMap<u64, Arc<Mutex<Slot>>>>,
+}
+
+/// Identifies servers that can be shared. Remote servers, and local ones that
+/// asked to be isolated, get one instance per session. So do sessions with
+/// roots of their own, as they'd otherwise be offered to each other's
+/// servers.
+fn key(config: &McpServerConfig, roots: &[Root]) -> Option<u64> {
+    if config.is_remote() || config.isolated {
+        return None;
+    }
+
+    let env: BTreeMap<&String, &String> = config.env.iter().collect();
+
+    let mut hasher = DefaultHasher::new();
+    config.command.hash(&mut hasher);
+    config.args.hash(&mut hasher);
+    config.cwd.hash(&mut hasher);
+    config.container.hash(&mut hasher);
+    config.sandbox.hash(&mut hasher);
+    config.limits.hash(&mut hasher);
+    // Settings the server is run with, which every session sharing it gets.
+    config.sampling.hash(&mut hasher);
+    config.restart.hash(&mut hasher);
+    config.timeout_ms.hash(&mut hasher);
+    config.shutdown_grace_ms.hash(&mut hasher);
+    env.hash(&mut hasher);
+
+    let mut roots: Vec<&str> = roots.iter().map(|root| root.uri.as_str()).collect();
+    roots.sort_unstable();
+    roots.dedup();
+    roots.hash(&mut hasher);
+
+    Some(hasher.finish())
+}
+
+impl Pool {
+    /// Get a running server for `config` on behalf of session `session_id`,
+    /// starting one if there's none to share.
+    pub async fn acquire(
+        &self,
+        session_id: i32,
+        config: McpServerConfig,
+        app: AppHandle,
+    ) -> Result<Arc<McpServer>> {
+        let roots = app
+            .state::<State>()
+            .sessions
+            .lock()
+            .await
+            .get(&session_id)
+            .map(|session| session.roots.clone())
+            .unwrap_or_default();
+
+        let Some(key) = key(&config, &roots) else {
+            let server = Arc::new(McpServer::start(config, app).await?);
+            health::watch(&server);
+            return Ok(server);
+        };
+
+        let entry = self.slots.lock().await.entry(key).or_default().clone();
+
+        // Held while starting, so that sessions asking for the same server at
+        // the same time wait for the one we're starting.
+        let mut slot = entry.lock().await;
+
+        if slot.sessions.contains(&session_id) {
+            return Err(anyhow!("This server is already running in session {}.", session_id));
+        }
+
+        let server = match slot.server.clone().filter(|server| server.is_alive()) {
+            Some(server) => server,
+            None => match McpServer::start(config, app).await {
+                Ok(server) => {
+                    let server = Arc::new(server.pooled(key));
+                    health::watch(&server);
+                    slot.server = Some(server.clone());
+                    server
+                }
+                Err(e) => {
+                    drop(slot);
+                    drop(entry);
+                    self.forget(key).await;
+                    return Err(e);
+                }
+            },
+        };
+
+        slot.sessions.insert(session_id);
+        Ok(server)
+    }
+
+    /// Let go of `server` on behalf of session `session_id`, shutting it down
+    /// if no other session is using it. Waits for it to exit, which may take
+    /// up to its grace period.
+    pub async fn release(&self, session_id: i32, server: Arc<McpServer>) -> Result<()> {
+        let Some(key) = server.pool_key() else {
+            return stop(session_id, &server).await;
+        };
+
+        let Some(entry) = self.slots.lock().await.get(&key).cloned() else {
+            return stop(session_id, &server).await;
+        };
+
+        let mut slot = entry.lock().await;
+        slot.sessions.remove(&session_id);
+
+        // The slot has since moved on to a replacement for this server, which
+        // means this one has died already.
+        if !slot.server.as_ref().is_some_and(|current| Arc::ptr_eq(current, &server)) {
+            drop(slot);
+            return stop(session_id, &server).await;
+        }
+
+        if slot.sessions.is_empty() {
+            slot.server = None;
+            drop(slot);
+            drop(entry);
+            self.forget(key).await;
+            return stop(session_id, &server).await;
+        }
+
+        server.detach(session_id);
+        Ok(())
+    }
+
+    /// Remove the slot for `key`, unless someone's using it, or on their way
+    /// to. They can only have got it from the map, which is locked, so every
+    /// one of them holds a reference to it.
+    async fn forget(&self, key: u64) {
+        let mut slots = self.slots.lock().await;
+        let Some(entry) = slots.get(&key) else {
+            return;
+        };
+
+        let idle = Arc::strong_count(entry) == 1
+            && entry
+                .try_lock()
+                .is_ok_and(|slot| slot.server.is_none() && slot.sessions.is_empty());
+
+        if idle {
+            slots.remove(&key);
+        }
+    }
+}
+
+/// Shut down `server`, which session `session_id` is the last to use. It's
+/// marked dead first, while the session still hears about it.
+async fn stop(session_id: i32, server: &McpServer) -> Result<()> {
+    server.set_status(Status::Dead, None);
+    server.detach(session_id);
+    server.shutdown().await
+}
@@ -1,109 +1,438 @@
-use std::collections::HashMap;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
-use rmcp::service::ServiceRole;
+use futures::SinkExt;
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use rmcp::transport::io::from_async_write;
 use rmcp::transport::IntoTransport;
+use rmcp::RoleClient;
+use serde::{Deserialize, Serialize};
 use sysinfo::Pid;
-use tauri::AppHandle;
-use tokio::io::AsyncRead;
-use tokio::process::{Child, ChildStdin, ChildStdout};
+use tauri::{AppHandle, Manager};
+use tokio::process::{ChildStdin, ChildStdout, Command};
+use tokio::sync::watch;
 
-use super::get_os_specific_command;
+use super::annotations::Annotations;
+use super::config::{ContainerConfig, McpServerConfig};
+use super::limits::{self, Limit};
+use super::logs::ServerLogs;
+use super::metadata;
+use super::sandbox;
+use super::transport::{from_lines, with_progress_token};
+use super::{get_os_specific_command, resolve_executable};
+use crate::state::State;
+
+/// How a server process ended.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Exit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    /// Set if the server was killed for hitting one of its resource limits.
+    pub limit: Option<Limit>,
+}
+
+impl Exit {
+    pub fn describe(&self) -> String {
+        match (self.limit, self.signal, self.code) {
+            (Some(limit), _, _) => limit.describe().to_string(),
+            (None, Some(signal), _) => format!("Killed by signal {}.", signal),
+            (None, None, Some(code)) => format!("Exited with code {}.", code),
+            (None, None, None) => "Exited.".to_string(),
+        }
+    }
+}
+
+/// Receives `Some(Exit)` once the process has been reaped.
+pub type ExitWatch = watch::Receiver<Option<Exit>>;
+
+/// A server running in a container. Killing the runtime's client doesn't
+/// necessarily take the container down with it, so it's stopped by name
+/// instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Container {
+    runtime: PathBuf,
+    name: String,
+}
+
+impl Container {
+    fn new(runtime: PathBuf) -> Self {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            runtime,
+            name: format!(
+                "tome-mcp-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::SeqCst)
+            ),
+        }
+    }
+
+    /// Stop the container, giving it `grace` to exit before the runtime kills
+    /// it.
+    pub async fn shutdown(&self, grace: Duration) {
+        let status = Command::new(&self.runtime)
+            .args(["stop", "--time", &grace.as_secs_f64().ceil().to_string(), &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+
+        if let Err(e) = status {
+            log::error!("Failed to stop container {}: {}", self.name, e);
+        }
+
+        // Containers run with `--rm`, so this is only for those that didn't
+        // stop.
+        self.stop();
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Remove the container, waiting on the runtime, for when there's no
+    /// async runtime left to wait on. Returns whether it's gone.
+    pub fn remove(&self) -> bool {
+        let status = std::process::Command::new(&self.runtime)
+            .args(["rm", "--force", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        match status {
+            Ok(status) if status.success() => true,
+            Ok(status) => {
+                log::error!("Failed to remove container {}: {}", self.name, status);
+                false
+            }
+            Err(e) => {
+                log::error!("Failed to remove container {}: {}", self.name, e);
+                false
+            }
+        }
+    }
+
+    pub fn stop(&self) {
+        let mut cmd = Command::new(&self.runtime);
+        cmd.args(["rm", "--force", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        let name = self.name.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = cmd.status().await {
+                log::error!("Failed to stop container {}: {}", name, e);
+            }
+        });
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct McpProcess {
-    pub child: Child,
+    pub pid: Pid,
     pub child_stdin: ChildStdin,
     pub child_stdout: ChildStdout,
+    pub exit: ExitWatch,
+    pub container: Option<Container>,
+    pub annotations: Annotations,
+}
+
+/// Build the command that launches a server, either directly or via a
+/// container runtime.
+fn command(config: &McpServerConfig, app: &AppHandle) -> Result<(Command, Option<Container>)> {
+    let Some(container) = &config.container else {
+        let mut args = config.args.clone();
+        if config.command.contains("npx") {
+            args.insert(0, "-y".to_string());
+        }
+
+        let mut cmd = get_os_specific_command(&config.command, app)?;
+        cmd.args(args).envs(&config.env);
+        return Ok((cmd, None));
+    };
+
+    let (cmd, runtime) = container_command(config, container)?;
+    Ok((cmd, Some(runtime)))
+}
+
+fn container_command(
+    config: &McpServerConfig,
+    container: &ContainerConfig,
+) -> Result<(Command, Container)> {
+    let runtime = Container::new(resolve_executable(&container.runtime)?);
+
+    let mut cmd = Command::new(&runtime.runtime);
+    cmd.args(["run", "-i", "--rm", "--name", &runtime.name]);
+
+    for volume in container.volumes.iter() {
+        cmd.args(["--volume", volume]);
+    }
+
+    // Values are handed to the runtime through its own environment, so they
+    // don't show up in the process list.
+    for key in config.env.keys().chain(container.pass_env.iter()) {
+        cmd.args(["--env", key]);
+    }
+    cmd.envs(&config.env);
+
+    if let Some(network) = &container.network {
+        cmd.args(["--network", network]);
+    }
+
+    if let Some(limits) = &config.limits {
+        cmd.args(limits::container_args(limits));
+    }
+
+    cmd.arg(&container.image).args(&config.args);
+
+    Ok((cmd, runtime))
 }
 
 impl McpProcess {
     pub fn start(
-        command: String,
-        args: Vec<String>,
-        env: HashMap<String, String>,
+        config: &McpServerConfig,
+        logs: &ServerLogs,
+        annotations: &Annotations,
         app: AppHandle,
     ) -> Result<Self> {
-        let mut cmd = get_os_specific_command(&command, &app)?;
-        let cmd = cmd.args(&args);
+        let (mut cmd, container) = command(config, &app)?;
+
+        if let Some(cwd) = &config.cwd {
+            cmd.current_dir(cwd);
+        }
 
-        log::info!("Executing command: {:?} {:?}", cmd, args);
+        let cgroup = match (&config.limits, &container) {
+            (Some(limits), None) => {
+                let cgroup = limits::apply(&mut cmd, limits)?;
+                logs.note(&format!(
+                    "Limited to: {}",
+                    limits::describe(limits, cgroup.as_ref())
+                ));
+                cgroup
+            }
+            _ => None,
+        };
 
-        cmd.kill_on_drop(true)
-            .envs(env)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped());
+        if let Some(sandbox) = &config.sandbox {
+            if container.is_some() {
+                return Err(anyhow!(
+                    "Container servers can't be sandboxed, the container is the sandbox."
+                ));
+            }
+
+            // Kept between runs, so that launchers needn't download the server
+            // every time.
+            let home = app
+                .path()
+                .app_cache_dir()?
+                .join("sandbox")
+                .join(format!("{:016x}", metadata::key(config)));
+
+            let program = PathBuf::from(cmd.as_std().get_program());
+            sandbox::apply(&mut cmd, sandbox, &program, &home)?;
+            logs.note(&format!(
+                "Sandboxed: {}",
+                sandbox::describe(sandbox, &program, &home)
+            ));
+        }
+
+        log::info!("Executing command: {:?}", cmd);
+
+        // In a group of its own, so that whatever it spawns can be killed along
+        // with it, even once it's been re-parented.
+        cmd.process_group(0)
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         let mut child = cmd.spawn()?;
 
+        let pid = child
+            .id()
+            .map(Pid::from_u32)
+            .ok_or_else(|| anyhow!("Child process does not have a PID"))?;
+
+        app.state::<State>()
+            .groups
+            .add(pid.as_u32(), container.as_ref());
+
         let child_stdin = child
             .stdin
             .take()
-            .ok_or_else(|| anyhow!("Failed to take stdin for command: {}", command))?;
+            .ok_or_else(|| anyhow!("Failed to take stdin for command: {}", config.command))?;
 
         let child_stdout = child
             .stdout
             .take()
-            .ok_or_else(|| anyhow!("Failed to take stdout for command: {}", command))?;
+            .ok_or_else(|| anyhow!("Failed to take stdout for command: {}", config.command))?;
+
+        let child_stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow!("Failed to take stderr for command: {}", config.command))?;
+
+        logs.capture(child_stderr, Some(pid.as_u32()));
+
+        // The child lives in this task until it exits, so that someone is
+        // always around to reap it and report how it went.
+        let (tx, exit) = watch::channel(None);
+        let sandboxed = config
+            .sandbox
+            .as_ref()
+            .is_some_and(|sandbox| sandbox.seccomp);
+        let limits = config.limits.clone().unwrap_or_default();
+        let logs = logs.clone();
+        tokio::spawn(async move {
+            // Only known until the child's reaped.
+            let cpu = match limits.cpu_seconds {
+                Some(_) => {
+                    tokio::task::spawn_blocking(move || limits::cpu_time_at_exit(pid.as_u32()))
+                        .await
+                        .ok()
+                        .flatten()
+                }
+                None => None,
+            };
+
+            let mut exit = match child.wait().await {
+                Ok(status) => Exit {
+                    code: status.code(),
+                    signal: status.signal(),
+                    limit: None,
+                },
+                Err(e) => {
+                    log::error!("Failed to wait on MCP server {}: {}", pid, e);
+                    Exit {
+                        code: None,
+                        signal: None,
+                        limit: None,
+                    }
+                }
+            };
+
+            exit.limit = limits::exceeded(&limits, cgroup.as_ref(), exit.signal, cpu);
+            if let Some(cgroup) = cgroup {
+                cgroup.remove().await;
+            }
+
+            log::info!("MCP server {} exited: {:?}", pid, exit);
+
+            if sandboxed && exit.signal == Some(libc::SIGSYS) {
+                logs.note("Killed by the sandbox, for making a blocked system call.");
+            }
+
+            if let Some(limit) = exit.limit {
+                logs.note(limit.describe());
+            }
+
+            let _ = tx.send(Some(exit));
+        });
 
         Ok(Self {
-            child,
+            pid,
             child_stdin,
             child_stdout,
+            exit,
+            container,
+            annotations: annotations.clone(),
         })
     }
 
-    pub fn pid(&self) -> Result<Pid> {
-        self.child
-            .id()
-            .map(Pid::from_u32)
-            .ok_or_else(|| anyhow!("Child process does not have a PID"))
+    pub fn split(self) -> (ChildStdout, ChildStdin, Annotations) {
+        (self.child_stdout, self.child_stdin, self.annotations)
     }
+}
 
-    pub fn split(self) -> (McpProcessOut, ChildStdin) {
-        let McpProcess {
-            child,
-            child_stdin,
-            child_stdout,
-        } = self;
+impl IntoTransport<RoleClient, std::io::Error, ()> for McpProcess {
+    fn into_transport(
+        self,
+    ) -> (
+        impl futures::Sink<ClientJsonRpcMessage, Error = std::io::Error> + Send + 'static,
+        impl futures::Stream<Item = ServerJsonRpcMessage> + Send + 'static,
+    ) {
+        let (stdout, stdin, annotations) = self.split();
 
-        (
-            McpProcessOut {
-                child,
-                child_stdout,
-            },
-            child_stdin,
-        )
-    }
-}
+        let sink = from_async_write(stdin).with(|message: ClientJsonRpcMessage| {
+            futures::future::ready(with_progress_token(&message).map_err(std::io::Error::from))
+        });
 
-pin_project_lite::pin_project! {
-    pub(crate) struct McpProcessOut {
-        child: Child,
-        #[pin]
-        child_stdout: ChildStdout,
+        (sink, from_lines(stdout, annotations))
     }
 }
 
-impl AsyncRead for McpProcessOut {
-    fn poll_read(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &mut tokio::io::ReadBuf<'_>,
-    ) -> std::task::Poll<std::io::Result<()>> {
-        self.project().child_stdout.poll_read(cx, buf)
-    }
-}
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
 
-impl<R: ServiceRole> IntoTransport<R, std::io::Error, ()> for McpProcess {
-    fn into_transport(
-        self,
-    ) -> (
-        impl futures::Sink<rmcp::service::TxJsonRpcMessage<R>, Error = std::io::Error> + Send + 'static,
-        impl futures::Stream<Item = rmcp::service::RxJsonRpcMessage<R>> + Send + 'static,
-    ) {
-        IntoTransport::<R, std::io::Error, rmcp::transport::io::TransportAdapterAsyncRW>::into_transport(
-            self.split(),
+    use super::*;
+
+    /// Points the runtime at a script that logs how it was called, one line
+    /// per call.
+    #[tokio::test]
+    async fn container_runtime_is_called_as_expected() {
+        let dir = std::env::temp_dir().join(format!("tome-fake-runtime-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let calls = dir.join("calls");
+        let runtime = dir.join("docker");
+        fs::write(
+            &runtime,
+            format!("#!/bin/sh\necho \"$@\" >> '{}'\n", calls.display()),
         )
+        .unwrap();
+        fs::set_permissions(&runtime, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = McpServerConfig {
+            args: vec!["--verbose".to_string()],
+            env: HashMap::from([("TOKEN".to_string(), "secret".to_string())]),
+            container: Some(ContainerConfig {
+                runtime: runtime.display().to_string(),
+                image: "mcp/fetch".to_string(),
+                volumes: vec!["/data:/data:ro".to_string()],
+                pass_env: vec!["HOME".to_string()],
+                network: Some("none".to_string()),
+            }),
+            ..Default::default()
+        };
+
+        let (mut cmd, container) =
+            container_command(&config, config.container.as_ref().unwrap()).unwrap();
+        assert!(cmd.status().await.unwrap().success());
+
+        // Doesn't wait for the runtime.
+        container.stop();
+        let mut lines = vec![];
+        for _ in 0..50 {
+            lines = fs::read_to_string(&calls)
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect();
+            if lines.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        let name = container.name();
+        assert_eq!(
+            lines,
+            [
+                format!(
+                    "run -i --rm --name {} --volume /data:/data:ro --env TOKEN --env HOME \
+                     --network none mcp/fetch --verbose",
+                    name
+                ),
+                format!("rm --force {}", name),
+            ]
+        );
     }
 }
@@ -0,0 +1,33 @@
+//! Sampling lets a server ask us for an LLM completion of its own. Engines
+//! live in the frontend, so requests are handed over as `mcp/sampling` events,
+//! and answered through `respond_mcp_sampling` once the user has approved
+//! them and an engine has replied.
+
+use rmcp::model::{CreateMessageRequestParam, CreateMessageResult};
+use serde::{Deserialize, Serialize};
+
+use super::config::SamplingConfig;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingRequest {
+    pub id: String,
+    /// The tool call the request was made during, if we can tell.
+    pub tool_call_id: Option<String>,
+    pub sampling: SamplingConfig,
+    pub request: CreateMessageRequestParam,
+}
+
+#[derive(Clone, Serialize)]
+pub struct SamplingCancelled {
+    pub id: String,
+}
+
+/// The frontend's answer to a `SamplingRequest`: either the engine's reply,
+/// or why there isn't one (it was denied, over budget, or the engine failed).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SamplingResponse {
+    Result(CreateMessageResult),
+    Error(String),
+}
@@ -0,0 +1,416 @@
+//! Confines local servers to the files they've been given, and optionally
+//! cuts them off from the network, using landlock, seccomp and a fresh network
+//! namespace. Everything is set up in the child between `fork` and `exec`.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use libc::{c_long, sock_filter};
+use tokio::process::Command;
+
+use super::config::SandboxConfig;
+
+/// Paths every server needs to read to get off the ground.
+const SYSTEM_PATHS: [&str; 11] = [
+    "/usr", "/lib", "/lib64", "/lib32", "/bin", "/sbin", "/etc", "/opt", "/nix", "/proc", "/sys",
+];
+
+/// Paths every server may write to.
+const SCRATCH_PATHS: [&str; 2] = ["/tmp", "/dev"];
+
+/// Where programs look for their caches, data and settings, relative to the
+/// home directory. Launchers like `npx` and `uvx` won't run without
+/// somewhere to keep theirs.
+const XDG_DIRS: [(&str, &str); 4] = [
+    ("XDG_CACHE_HOME", ".cache"),
+    ("XDG_CONFIG_HOME", ".config"),
+    ("XDG_DATA_HOME", ".local/share"),
+    ("XDG_STATE_HOME", ".local/state"),
+];
+
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+
+const ACCESS_EXECUTE: u64 = 1 << 0;
+const ACCESS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_READ_FILE: u64 = 1 << 2;
+const ACCESS_READ_DIR: u64 = 1 << 3;
+/// Since ABI 2.
+const ACCESS_REFER: u64 = 1 << 13;
+/// Since ABI 3.
+const ACCESS_TRUNCATE: u64 = 1 << 14;
+/// Since ABI 5.
+const ACCESS_IOCTL_DEV: u64 = 1 << 15;
+
+/// Every filesystem right in the first landlock ABI.
+const ACCESS_V1: u64 = (1 << 13) - 1;
+const ACCESS_READ: u64 = ACCESS_EXECUTE | ACCESS_READ_FILE | ACCESS_READ_DIR;
+/// The only rights that make sense on a file, as opposed to a directory.
+const ACCESS_FILE: u64 =
+    ACCESS_EXECUTE | ACCESS_WRITE_FILE | ACCESS_READ_FILE | ACCESS_TRUNCATE | ACCESS_IOCTL_DEV;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// System calls no MCP server has any business making. Making one kills the
+/// server, which we then report in its log.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const BLOCKED_SYSCALLS: [c_long; 22] = [
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_acct,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+];
+
+/// A filesystem rule, ready to be applied without allocating.
+struct Rule {
+    path: CString,
+    access: u64,
+}
+
+/// Run `cmd` inside the sandbox described by `config`. `program` is the
+/// executable being run, whose directory is readable on top of the usual
+/// system paths.
+///
+/// The server gets `home` as a home directory of its own. Anything it leaves
+/// in the real one, say a systemd unit or an autostart entry, would later run
+/// outside the sandbox.
+pub fn apply(cmd: &mut Command, config: &SandboxConfig, program: &Path, home: &Path) -> Result<()> {
+    let abi = landlock_abi();
+    if abi < 1 {
+        return Err(anyhow!(
+            "Sandboxing needs landlock, which this kernel doesn't support."
+        ));
+    }
+    let all = access_all(abi);
+
+    let mut rules = vec![];
+    let mut allow = |path: &Path, access: u64| -> Result<()> {
+        // Rules can only point at things that exist.
+        let Ok(metadata) = path.metadata() else {
+            return Ok(());
+        };
+        let access = if metadata.is_dir() {
+            access
+        } else {
+            access & ACCESS_FILE
+        };
+        rules.push(Rule {
+            path: CString::new(path.as_os_str().as_bytes())?,
+            access,
+        });
+        Ok(())
+    };
+
+    for path in SYSTEM_PATHS {
+        allow(Path::new(path), ACCESS_READ)?;
+    }
+    if let Some(dir) = program.parent() {
+        allow(dir, ACCESS_READ)?;
+    }
+    for path in config.read.iter() {
+        allow(Path::new(path), ACCESS_READ)?;
+    }
+
+    cmd.env("HOME", home);
+    for (var, dir) in XDG_DIRS {
+        let dir = home.join(dir);
+        std::fs::create_dir_all(&dir)?;
+        cmd.env(var, dir);
+    }
+
+    for path in SCRATCH_PATHS.iter().map(Path::new).chain([home]) {
+        allow(path, all)?;
+    }
+    for path in config.write.iter() {
+        allow(Path::new(path), all)?;
+    }
+
+    let namespace = match config.no_network {
+        true => Some(Namespace::new()?),
+        false => None,
+    };
+
+    let mut filter = match config.seccomp {
+        true => Some(seccomp_filter()?),
+        false => None,
+    };
+
+    // SAFETY: runs in the child between fork and exec, where nothing may
+    // allocate or take a lock. Entering the namespaces writes the id maps
+    // built in `Namespace::new`, landlock gets the rules built above, paths
+    // and all, and seccomp the filter, so it's raw system calls throughout.
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(namespace) = &namespace {
+                namespace.enter()?;
+            }
+
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            restrict_filesystem(all, &rules)?;
+
+            if let Some(filter) = filter.as_mut() {
+                let program = libc::sock_fprog {
+                    len: filter.len() as libc::c_ushort,
+                    filter: filter.as_mut_ptr(),
+                };
+                check(libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const libc::sock_fprog,
+                ))?;
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Describe the sandbox `apply` set up, for the server's log, so that
+/// permission errors it prints can be told apart from bugs of its own.
+pub fn describe(config: &SandboxConfig, program: &Path, home: &Path) -> String {
+    let read = SYSTEM_PATHS
+        .iter()
+        .map(|path| path.to_string())
+        .chain(
+            program
+                .parent()
+                .filter(|dir| !SYSTEM_PATHS.iter().any(|path| dir.starts_with(path)))
+                .map(|dir| dir.display().to_string()),
+        )
+        .chain(config.read.iter().cloned());
+    let write = SCRATCH_PATHS
+        .iter()
+        .map(|path| path.to_string())
+        .chain([home.display().to_string()])
+        .chain(config.write.iter().cloned());
+
+    let mut parts = vec![format!(
+        "may read [{}] and write [{}], anything else fails with \"Permission denied\" (EACCES)",
+        read.collect::<Vec<_>>().join(", "),
+        write.collect::<Vec<_>>().join(", ")
+    )];
+    if config.no_network {
+        parts.push("network disabled".to_string());
+    }
+    if config.seccomp {
+        parts.push("system calls filtered, making one kills the server".to_string());
+    }
+    parts.join("; ")
+}
+
+fn landlock_abi() -> c_long {
+    // SAFETY: asks for the ABI version, with no attributes to read.
+    unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    }
+}
+
+pub(super) fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Every filesystem right landlock knows about at `abi`. Those it doesn't
+/// can't be taken away, and are allowed everywhere.
+fn access_all(abi: c_long) -> u64 {
+    let mut access = ACCESS_V1;
+    if abi >= 2 {
+        access |= ACCESS_REFER;
+    }
+    if abi >= 3 {
+        access |= ACCESS_TRUNCATE;
+    }
+    // ABI 4 only added network rights.
+    if abi >= 5 {
+        access |= ACCESS_IOCTL_DEV;
+    }
+    access
+}
+
+/// Confine the calling process for good, so only for the child, between fork
+/// and exec. `handled` are the rights denied everywhere but where `rules`
+/// allow them.
+unsafe fn restrict_filesystem(handled: u64, rules: &[Rule]) -> io::Result<()> {
+    let attr = RulesetAttr {
+        handled_access_fs: handled,
+    };
+    let ruleset = libc::syscall(
+        libc::SYS_landlock_create_ruleset,
+        &attr as *const RulesetAttr,
+        std::mem::size_of::<RulesetAttr>(),
+        0,
+    ) as libc::c_int;
+    check(ruleset)?;
+
+    for rule in rules {
+        let fd = libc::open(rule.path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC);
+        if fd < 0 {
+            continue;
+        }
+
+        let attr = PathBeneathAttr {
+            allowed_access: rule.access,
+            parent_fd: fd,
+        };
+        let result = libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset,
+            LANDLOCK_RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0,
+        );
+        libc::close(fd);
+        check(result as libc::c_int)?;
+    }
+
+    let result = libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0);
+    libc::close(ruleset);
+    check(result as libc::c_int)
+}
+
+/// A user namespace, so that we're allowed a network namespace of our own,
+/// that maps us onto ourselves.
+struct Namespace {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+impl Namespace {
+    fn new() -> Result<Self> {
+        // SAFETY: these can't fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Ok(Self {
+            uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+        })
+    }
+
+    /// Move the calling process into the namespaces, for good, so only for the
+    /// child, between fork and exec. The kernel won't make a user namespace
+    /// for a process with more than one thread anyway.
+    unsafe fn enter(&self) -> io::Result<()> {
+        check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET))?;
+        write(c"/proc/self/setgroups", b"deny")?;
+        write(c"/proc/self/uid_map", &self.uid_map)?;
+        write(c"/proc/self/gid_map", &self.gid_map)
+    }
+}
+
+/// Write `data` to `path` with raw system calls, as `std::fs` may allocate,
+/// which the child mustn't between fork and exec.
+unsafe fn write(path: &std::ffi::CStr, data: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+    check(fd)?;
+    let written = libc::write(fd, data.as_ptr() as *const libc::c_void, data.len());
+    libc::close(fd);
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn statement(code: u32, k: u32) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// A BPF program that kills the process on any of `BLOCKED_SYSCALLS`, or on
+/// system calls made through a foreign ABI, and allows everything else.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn seccomp_filter() -> Result<Vec<sock_filter>> {
+    use libc::{BPF_ABS, BPF_JEQ, BPF_JGE, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+
+    const ARCH_OFFSET: u32 = 4;
+    const NR_OFFSET: u32 = 0;
+    /// x32 system calls share our architecture, but not our numbering.
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    let blocked = BLOCKED_SYSCALLS.len() as u8;
+    let mut filter = vec![
+        statement(BPF_LD | BPF_W | BPF_ABS, ARCH_OFFSET),
+        jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+        statement(BPF_RET | BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        statement(BPF_LD | BPF_W | BPF_ABS, NR_OFFSET),
+        jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, blocked + 1, 0),
+    ];
+
+    for (i, nr) in BLOCKED_SYSCALLS.iter().enumerate() {
+        // Jump past the remaining checks and the allow, to the kill.
+        filter.push(jump(
+            BPF_JMP | BPF_JEQ | BPF_K,
+            *nr as u32,
+            blocked - i as u8,
+            0,
+        ));
+    }
+
+    filter.push(statement(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+    filter.push(statement(BPF_RET | BPF_K, libc::SECCOMP_RET_KILL_PROCESS));
+
+    Ok(filter)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn seccomp_filter() -> Result<Vec<sock_filter>> {
+    Err(anyhow!(
+        "System call filtering isn't supported on this architecture."
+    ))
+}
@@ -1,79 +1,474 @@
-use std::collections::HashMap;
-
 use crate::process::Process;
+use crate::state::State;
+
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use rmcp::model::{CallToolRequestParam, RawContent, Tool};
-use rmcp::service::ServiceRole;
+use anyhow::{anyhow, Result};
+use rmcp::model::{
+    CallToolRequest, CallToolRequestParam, CancelledNotificationParam, ClientRequest,
+    GetPromptRequestParam, JsonObject, LoggingLevel, PingRequest, Prompt, ReadResourceRequestParam,
+    ReadResourceResult, Resource, ResourceTemplate, ServerResult, SetLevelRequestParam,
+    SubscribeRequestParam, Tool, UnsubscribeRequestParam,
+};
+use rmcp::service::{PeerRequestOptions, RequestHandle, ServiceError, ServiceRole};
+use rmcp::transport::IntoTransport;
 use rmcp::ServiceExt;
 use rmcp::{service::RunningService, RoleClient};
 use sysinfo::Pid;
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+use super::annotations::{Annotations, ToolAnnotations};
+use super::client::McpClient;
+use super::config::{McpServerConfig, TransportKind};
+use super::content::{PromptResult, ToolResult};
+use super::health::{Health, Status, PING_TIMEOUT};
+use super::logs::ServerLogs;
+use super::metrics::{Metrics, Outcome};
+use super::orphans::Groups;
+use super::process::{Container, ExitWatch, McpProcess};
+use super::transport::{sse, streamable_http};
+
+type Service = RunningService<RoleClient, McpClient>;
+
+/// How long a server has to finish initializing. Generous, as launchers like
+/// `npx` may have to download the server first.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Serve `transport`, giving up on servers that don't finish initializing in
+/// time.
+async fn handshake<T, E, A>(
+    client: McpClient,
+    transport: T,
+    ct: CancellationToken,
+) -> Result<Service>
+where
+    T: IntoTransport<RoleClient, E, A>,
+    E: std::error::Error + From<std::io::Error> + Send + Sync + 'static,
+{
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, client.serve_with_ct(transport, ct)).await {
+        Ok(service) => Ok(service?),
+        Err(_) => Err(anyhow!(
+            "Server didn't finish starting within {}s.",
+            HANDSHAKE_TIMEOUT.as_secs()
+        )),
+    }
+}
 
-use super::process::McpProcess;
+/// Ways a tool call can end without the server answering. The prefixes let
+/// the frontend tell them apart from ordinary failures.
+#[derive(Debug, Error)]
+pub enum CallError {
+    #[error("TimeoutError: tool call timed out after {}ms", .0.as_millis())]
+    Timeout(Duration),
 
-type Service = RunningService<RoleClient, ()>;
+    #[error("CancelledError: tool call was cancelled")]
+    Cancelled,
+}
 
 #[derive(Debug)]
 pub struct McpServer {
     service: Service,
-    pid: Pid,
-    custom_name: Option<String>,
+    config: McpServerConfig,
+    pid: Option<Pid>,
+    exit: Option<ExitWatch>,
+    container: Option<Container>,
+    ct: CancellationToken,
+    logs: ServerLogs,
+    annotations: Annotations,
+    metrics: Metrics,
+    groups: Groups,
+    health: Health,
+    started: Instant,
+    /// Where the pool keeps the server, if it's shared between sessions.
+    pool_key: Option<u64>,
 }
 
 impl McpServer {
-    pub async fn start(
-        command: String,
-        mut args: Vec<String>,
-        env: HashMap<String, String>,
-        app: AppHandle,
-    ) -> Result<Self> {
-        if command.contains("npx") {
-            args.insert(0, "-y".to_string());
-        }
+    pub async fn start(config: McpServerConfig, app: AppHandle) -> Result<Self> {
+        let ct = CancellationToken::new();
+        let logs = ServerLogs::default();
+        let annotations = Annotations::default();
+        let metrics = app.state::<State>().metrics.clone();
+        let groups = app.state::<State>().groups.clone();
+        let client = McpClient::new(app.clone(), config.sampling.clone());
+        let mut container = None;
+
+        let (service, pid, exit) = match config.transport {
+            TransportKind::Stdio => {
+                let proc = McpProcess::start(&config, &logs, &annotations, app)?;
+                let pid = proc.pid;
+                let exit = proc.exit.clone();
+                container = proc.container.clone();
+                match handshake(client, proc, ct.clone()).await {
+                    Ok(service) => (service, Some(pid), Some(exit)),
+                    Err(e) => {
+                        // The child lives in its wait task, so nothing kills it
+                        // on drop.
+                        ct.cancel();
+                        if let Some(container) = &container {
+                            container.stop();
+                        }
+                        Process { pid }.signal_group(libc::SIGKILL);
+                        groups.remove(pid.as_u32());
+                        return Err(logs.annotate(e).await);
+                    }
+                }
+            }
+            TransportKind::Sse => {
+                log::info!("Connecting to SSE server: {:?}", config.url);
+                let transport =
+                    sse(config.url()?, config.http_client()?, annotations.clone()).await?;
+                (handshake(client, transport, ct.clone()).await?, None, None)
+            }
+            TransportKind::StreamableHttp => {
+                log::info!("Connecting to Streamable HTTP server: {:?}", config.url);
+                let transport =
+                    streamable_http(config.url()?, config.http_client()?, annotations.clone());
+                (handshake(client, transport, ct.clone()).await?, None, None)
+            }
+        };
 
-        let proc = McpProcess::start(command, args, env, app)?;
-        let pid = proc.pid()?;
-        let service = ().serve(proc).await?;
         Ok(Self {
             service,
+            config,
             pid,
-            custom_name: None,
+            exit,
+            container,
+            ct,
+            logs,
+            annotations,
+            metrics,
+            groups,
+            health: Health::default(),
+            started: Instant::now(),
+            pool_key: None,
         })
     }
 
     pub fn name(&self) -> String {
-        self.custom_name
-            .clone()
-            .unwrap_or_else(|| self.peer_info().server_info.name.clone())
+        self.peer_info().server_info.name.clone()
+    }
+
+    pub fn config(&self) -> &McpServerConfig {
+        &self.config
+    }
+
+    /// Mark the server as shared, and kept under `key`.
+    pub fn pooled(mut self, key: u64) -> Self {
+        self.pool_key = Some(key);
+        self
+    }
+
+    pub fn pool_key(&self) -> Option<u64> {
+        self.pool_key
+    }
+
+    pub fn pid(&self) -> Option<Pid> {
+        self.pid
+    }
+
+    /// Watches the process for exit. Only local servers have one.
+    pub fn exit(&self) -> Option<ExitWatch> {
+        self.exit.clone()
+    }
+
+    /// Cancelled once we've asked this server to shut down, as opposed to it
+    /// dying on its own.
+    pub fn stopping(&self) -> CancellationToken {
+        self.ct.clone()
+    }
+
+    /// Tag the server's logs and events with the session and name it's
+    /// running as.
+    pub fn attach(&self, session_id: i32, name: &str) {
+        self.logs.attach(session_id, name);
+        self.service.service().attach(session_id, name);
+
+        if self.status() == Status::Starting {
+            self.set_status(Status::Ready, None);
+        }
+    }
+
+    pub fn detach(&self, session_id: i32) {
+        self.logs.detach(session_id);
+        self.service.service().detach(session_id);
+    }
+
+    /// What the server says about one of its tools.
+    pub fn annotations(&self, tool: &str) -> Option<ToolAnnotations> {
+        self.annotations.get(tool)
+    }
+
+    pub fn notify_roots_changed(&self) {
+        self.service.service().notify_roots_changed();
+    }
+
+    pub fn status(&self) -> Status {
+        self.health.status()
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.health.last_error()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Move the server to `status`, letting the sessions it's attached to
+    /// know if that's a change.
+    pub fn set_status(&self, status: Status, error: Option<String>) {
+        if let Some(change) = self.health.set(status, error) {
+            log::info!(
+                "MCP server '{}' is now {:?} (was {:?})",
+                self.name(),
+                change.status,
+                change.previous
+            );
+            self.service.service().emit("mcp/server-status", change);
+        }
+    }
+
+    /// Check the server is still answering requests.
+    pub async fn ping(&self) -> Result<()> {
+        let request = ClientRequest::PingRequest(PingRequest {
+            method: Default::default(),
+        });
+
+        match tokio::time::timeout(PING_TIMEOUT, self.service.send_request(request)).await {
+            Ok(response) => response.map(|_| ()).map_err(Into::into),
+            Err(_) => Err(anyhow!("No answer to ping after {}s", PING_TIMEOUT.as_secs())),
+        }
+    }
+
+    /// Whether the server is still up, as far as we know.
+    pub fn is_alive(&self) -> bool {
+        let exited = self.exit.as_ref().is_some_and(|exit| exit.borrow().is_some());
+        !exited && !self.ct.is_cancelled()
+    }
+
+    pub fn logs(&self) -> &ServerLogs {
+        &self.logs
     }
 
     pub fn peer_info(&self) -> <RoleClient as ServiceRole>::PeerInfo {
         self.service.peer_info().clone()
     }
 
+    /// The server's tools. Listed once, then kept up to date by
+    /// `tools/list_changed` notifications.
     pub async fn tools(&self) -> Result<Vec<Tool>> {
-        Ok(self.service.list_all_tools().await?)
+        if let Some(tools) = self.service.service().cached_tools() {
+            return Ok(tools);
+        }
+
+        let tools = self.service.list_all_tools().await?;
+        self.service.service().cache_tools(tools.clone());
+        Ok(tools)
     }
 
-    pub async fn call_tool(&self, request: CallToolRequestParam) -> Result<String> {
+    pub fn supports_resources(&self) -> bool {
+        self.service.peer_info().capabilities.resources.is_some()
+    }
+
+    pub async fn resources(&self) -> Result<Vec<Resource>> {
+        Ok(self.service.list_all_resources().await?)
+    }
+
+    pub async fn resource_templates(&self) -> Result<Vec<ResourceTemplate>> {
+        Ok(self.service.list_all_resource_templates().await?)
+    }
+
+    pub async fn read_resource(&self, uri: String) -> Result<ReadResourceResult> {
         Ok(self
             .service
-            .call_tool(request)
-            .await?
-            .content
-            .iter()
-            .filter_map(|r| match r.raw.clone() {
-                RawContent::Text(t) => Some(t.text),
-                _ => None,
+            .read_resource(ReadResourceRequestParam { uri })
+            .await?)
+    }
+
+    pub async fn subscribe(&self, uri: String) -> Result<()> {
+        Ok(self.service.subscribe(SubscribeRequestParam { uri }).await?)
+    }
+
+    pub async fn unsubscribe(&self, uri: String) -> Result<()> {
+        Ok(self
+            .service
+            .unsubscribe(UnsubscribeRequestParam { uri })
+            .await?)
+    }
+
+    pub fn supports_prompts(&self) -> bool {
+        self.service.peer_info().capabilities.prompts.is_some()
+    }
+
+    pub async fn prompts(&self) -> Result<Vec<Prompt>> {
+        Ok(self.service.list_all_prompts().await?)
+    }
+
+    pub async fn get_prompt(&self, name: String, arguments: JsonObject) -> Result<PromptResult> {
+        Ok(self
+            .service
+            .get_prompt(GetPromptRequestParam {
+                name,
+                arguments: Some(arguments),
             })
-            .collect::<Vec<String>>()
-            .join("\n"))
+            .await?
+            .into())
+    }
+
+    /// Call a tool, giving up once `timeout` elapses or `cancel` fires. In
+    /// either case the server is told to stop working on it.
+    ///
+    /// Progress and log notifications sent while the call is running are
+    /// tagged with `call_id`, and sent to session `session_id`. Metrics are
+    /// kept under `server`, the name the session runs the server under.
+    pub async fn call_tool(
+        &self,
+        server: &str,
+        session_id: i32,
+        request: CallToolRequestParam,
+        call_id: Option<String>,
+        timeout: Option<Duration>,
+        cancel: CancellationToken,
+    ) -> Result<ToolResult> {
+        let tool = request.name.to_string();
+        let started = Instant::now();
+
+        let result = self.send_tool_call(session_id, request, call_id, timeout, cancel).await;
+
+        let version = &self.peer_info().server_info.version;
+        self.metrics.record(server, version, &tool, started.elapsed(), Outcome::of(&result));
+
+        result
+    }
+
+    async fn send_tool_call(
+        &self,
+        session_id: i32,
+        request: CallToolRequestParam,
+        call_id: Option<String>,
+        timeout: Option<Duration>,
+        cancel: CancellationToken,
+    ) -> Result<ToolResult> {
+        let RequestHandle { rx, peer, id, .. } = self
+            .service
+            .send_cancellable_request(
+                ClientRequest::CallToolRequest(CallToolRequest {
+                    method: Default::default(),
+                    params: request,
+                }),
+                PeerRequestOptions::no_options(),
+            )
+            .await?;
+
+        if let Some(call_id) = call_id {
+            self.service.service().track(id.clone(), session_id, call_id);
+        }
+
+        let deadline = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let outcome = tokio::select! {
+            response = rx => Ok(response),
+            _ = deadline => Err(CallError::Timeout(timeout.unwrap_or_default())),
+            _ = cancel.cancelled() => Err(CallError::Cancelled),
+        };
+
+        self.service.service().untrack(&id);
+
+        match outcome {
+            Ok(response) => {
+                let response = response
+                    .map_err(|_| ServiceError::Transport(std::io::Error::other("disconnected")))??;
+                match response {
+                    ServerResult::CallToolResult(result) => Ok(result.into()),
+                    _ => Err(ServiceError::UnexpectedResponse.into()),
+                }
+            }
+            Err(error) => {
+                let notification = CancelledNotificationParam {
+                    request_id: id,
+                    reason: Some(error.to_string()),
+                };
+                if let Err(e) = peer.notify_cancelled(notification).await {
+                    log::warn!("Failed to notify {} of cancelled tool call: {}", self.name(), e);
+                }
+                Err(error.into())
+            }
+        }
     }
 
+    pub async fn set_log_level(&self, level: LoggingLevel) -> Result<()> {
+        Ok(self.service.set_level(SetLevelRequestParam { level }).await?)
+    }
+
+    /// Ask the server to exit, killing it if it hasn't within its grace
+    /// period.
+    ///
+    /// Stopping the service drops its transport, closing the server's stdin,
+    /// which is how MCP asks stdio servers to exit, so the server gets the
+    /// first half of its grace period to do so. SIGTERM is for those that
+    /// don't listen, and for anything they've spawned, which get the rest.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.ct.cancel();
+        let grace = self.config.shutdown_grace();
+
+        if let Some(container) = &self.container {
+            container.shutdown(grace).await;
+            if let Some(pid) = self.pid {
+                // Only the runtime's client, which has nothing left to do.
+                Process { pid }.signal_group(libc::SIGKILL);
+                self.groups.remove(pid.as_u32());
+            }
+            return Ok(());
+        }
+
+        let (Some(pid), Some(mut exit)) = (self.pid, self.exit.clone()) else {
+            return Ok(());
+        };
+
+        let deadline = Instant::now() + grace;
+        let _ = tokio::time::timeout(grace / 2, exit.wait_for(Option::is_some)).await;
+
+        let group = Process { pid };
+        group.signal_group(libc::SIGTERM);
+
+        let _ = tokio::time::timeout_at(deadline.into(), exit.wait_for(Option::is_some)).await;
+        while group.group_alive() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        if group.signal_group(libc::SIGKILL) {
+            log::warn!(
+                "Killed what was left of MCP server '{}' {}ms after asking it to exit",
+                self.name(),
+                grace.as_millis()
+            );
+        }
+        self.groups.remove(pid.as_u32());
+
+        Ok(())
+    }
+
+    /// Kill the server outright, without giving it a chance to clean up.
     pub fn kill(&self) -> Result<()> {
-        let process = Process { pid: self.pid };
-        process.kill_tree()?;
+        self.ct.cancel();
+
+        if let Some(container) = &self.container {
+            container.stop();
+        }
+
+        if let Some(pid) = self.pid {
+            Process { pid }.signal_group(libc::SIGKILL);
+            self.groups.remove(pid.as_u32());
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,296 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sysinfo::Pid;
+use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+
+use crate::state::State;
+
+use super::config::{McpServerConfig, RestartPolicy};
+use super::emit;
+use super::limits::Limit;
+use super::process::{Exit, ExitWatch};
+use super::server::McpServer;
+
+/// A server that stays up this long is considered healthy again, and gets a
+/// fresh set of restart attempts.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExitedEvent {
+    session_id: i32,
+    server: String,
+    pid: Option<u32>,
+    code: Option<i32>,
+    signal: Option<i32>,
+    /// Set if the server was killed for hitting one of its resource limits,
+    /// rather than crashing on its own.
+    limit: Option<Limit>,
+    restarting: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestartedEvent {
+    session_id: i32,
+    server: String,
+    pid: Option<u32>,
+    attempt: u32,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestartFailedEvent {
+    session_id: i32,
+    server: String,
+    attempts: u32,
+    error: String,
+}
+
+/// Everything we need to keep an eye on a server after it's been handed off
+/// to its session.
+struct Watched {
+    exit: ExitWatch,
+    stopping: CancellationToken,
+    pid: Option<Pid>,
+    config: McpServerConfig,
+}
+
+impl Watched {
+    fn new(server: &McpServer) -> Option<Self> {
+        Some(Self {
+            exit: server.exit()?,
+            stopping: server.stopping(),
+            pid: server.pid(),
+            config: server.config().clone(),
+        })
+    }
+}
+
+/// Watch a local server for unexpected exits. Crashed servers are removed from
+/// their session, reported via `mcp/server-exited`, and restarted if their
+/// config asks for it.
+pub fn supervise(session_id: i32, server: &McpServer, app: AppHandle) {
+    if let Some(watched) = Watched::new(server) {
+        tauri::async_runtime::spawn(run(session_id, watched, app));
+    }
+}
+
+async fn run(session_id: i32, mut watched: Watched, app: AppHandle) {
+    let mut attempt = 0;
+
+    loop {
+        let started = Instant::now();
+
+        let exit = match watched.exit.wait_for(Option::is_some).await {
+            Ok(exit) => *exit,
+            Err(_) => return,
+        };
+
+        if watched.stopping.is_cancelled() {
+            return;
+        }
+
+        // Once stopped in this session, the server is none of our business,
+        // even if other sessions are still sharing it.
+        let Some(name) = remove(session_id, watched.pid, &app).await else {
+            return;
+        };
+
+        if started.elapsed() >= STABLE_AFTER {
+            attempt = 0;
+        }
+
+        let policy = watched
+            .config
+            .restart
+            .clone()
+            .filter(|policy| attempt < policy.max_restarts);
+
+        let Exit {
+            code,
+            signal,
+            limit,
+        } = exit.unwrap_or(Exit {
+            code: None,
+            signal: None,
+            limit: None,
+        });
+
+        match limit {
+            Some(limit) => log::warn!(
+                "MCP server '{}' in session {} was killed for exceeding its {:?} limit",
+                name,
+                session_id,
+                limit
+            ),
+            None => log::warn!(
+                "MCP server '{}' in session {} exited unexpectedly (code: {:?}, signal: {:?})",
+                name,
+                session_id,
+                code,
+                signal
+            ),
+        }
+
+        emit(
+            &app,
+            "mcp/server-exited",
+            ExitedEvent {
+                session_id,
+                server: name.clone(),
+                pid: watched.pid.map(|pid| pid.as_u32()),
+                code,
+                signal,
+                limit,
+                restarting: policy.is_some(),
+            },
+        );
+
+        let Some(policy) = policy else {
+            return;
+        };
+
+        match restart(session_id, &name, &watched.config, &policy, &mut attempt, &app).await {
+            Some(restarted) => watched = restarted,
+            None => return,
+        }
+    }
+}
+
+/// Drop a dead server from its session, returning the name it was running
+/// under (which may have changed via a rename since we started watching), or
+/// `None` if it's no longer part of the session.
+async fn remove(session_id: i32, pid: Option<Pid>, app: &AppHandle) -> Option<String> {
+    let state = app.state::<State>();
+
+    let (name, server) = {
+        let mut sessions = state.sessions.lock().await;
+        let session = sessions.get_mut(&session_id)?;
+
+        let name = session
+            .mcp_servers
+            .iter()
+            .find(|(_, server)| server.pid() == pid)
+            .map(|(name, _)| name.clone())?;
+
+        let server = session.deregister(&name)?;
+        (name, server)
+    };
+
+    // Make sure the service winds down, and that nothing it spawned outlives
+    // it.
+    if let Err(e) = state.pool.release(session_id, server).await {
+        log::warn!("Failed to clean up after MCP server '{}': {}", name, e);
+    }
+
+    Some(name)
+}
+
+async fn restart(
+    session_id: i32,
+    name: &str,
+    config: &McpServerConfig,
+    policy: &RestartPolicy,
+    attempt: &mut u32,
+    app: &AppHandle,
+) -> Option<Watched> {
+    let mut error = String::new();
+
+    while *attempt < policy.max_restarts {
+        tokio::time::sleep(policy.backoff(*attempt)).await;
+        *attempt += 1;
+
+        log::info!(
+            "Restarting MCP server '{}' in session {} (attempt {}/{})",
+            name,
+            session_id,
+            attempt,
+            policy.max_restarts
+        );
+
+        let state = app.state::<State>();
+
+        // Sessions sharing the server all restart it, but only the first to
+        // get here actually starts a new one.
+        let server = match state.pool.acquire(session_id, config.clone(), app.clone()).await {
+            Ok(server) => server,
+            Err(e) => {
+                log::warn!("Failed to restart MCP server '{}': {}", name, e);
+                error = e.to_string();
+                continue;
+            }
+        };
+
+        let tools = match server.tools().await {
+            Ok(tools) => tools,
+            Err(e) => {
+                log::warn!("Failed to list tools of restarted MCP server '{}': {}", name, e);
+                error = e.to_string();
+                let _ = state.pool.release(session_id, server).await;
+                continue;
+            }
+        };
+
+        let mut sessions = state.sessions.lock().await;
+
+        // The session was stopped, or the server started again by someone
+        // else, while we were backing off.
+        let Some(session) = sessions
+            .get_mut(&session_id)
+            .filter(|session| !session.mcp_servers.contains_key(name))
+        else {
+            drop(sessions);
+            let _ = state.pool.release(session_id, server).await;
+            return None;
+        };
+
+        // Another server has taken its tools' names in the meantime, which
+        // retrying won't fix.
+        if let Err(e) = session.register(name.to_string(), &server, tools) {
+            log::warn!("Failed to restart MCP server '{}': {}", name, e);
+            error = e.to_string();
+            drop(sessions);
+            let _ = state.pool.release(session_id, server).await;
+            break;
+        }
+
+        let watched = Watched::new(&server)?;
+        server.attach(session_id, name);
+
+        emit(
+            app,
+            "mcp/server-restarted",
+            RestartedEvent {
+                session_id,
+                server: name.to_string(),
+                pid: watched.pid.map(|pid| pid.as_u32()),
+                attempt: *attempt,
+            },
+        );
+
+        return Some(watched);
+    }
+
+    log::error!(
+        "Giving up on MCP server '{}' in session {} after {} restart attempts",
+        name,
+        session_id,
+        attempt
+    );
+
+    emit(
+        app,
+        "mcp/server-restart-failed",
+        RestartFailedEvent {
+            session_id,
+            server: name.to_string(),
+            attempts: *attempt,
+            error,
+        },
+    );
+
+    None
+}
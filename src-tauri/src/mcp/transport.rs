@@ -0,0 +1,412 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::{Sink, Stream, StreamExt};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, Response, StatusCode, Url};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientNotification, ErrorData, JsonRpcError, JsonRpcNotification,
+    JsonRpcVersion2_0, ServerJsonRpcMessage,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sse_stream::{Sse, SseStream};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use super::annotations::Annotations;
+
+const HEADER_SESSION_ID: &str = "Mcp-Session-Id";
+const EVENT_STREAM: &str = "text/event-stream";
+const JSON: &str = "application/json";
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("Request failed: {0}")]
+    Reqwest(#[from] reqwest::Error),
+
+    #[error("Failed to parse message: {0}")]
+    Serde(#[from] serde_json::Error),
+
+    #[error("Event stream failed: {0}")]
+    Sse(String),
+
+    #[error("Unexpected content type: {0}")]
+    UnexpectedContentType(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+type Events = Pin<Box<dyn Stream<Item = Result<Sse, sse_stream::Error>> + Send>>;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Batch {
+    One(ServerJsonRpcMessage),
+    Many(Vec<ServerJsonRpcMessage>),
+}
+
+/// Shared half of a Streamable HTTP connection. Every outgoing message is a
+/// POST to the same endpoint; the server answers with either plain JSON or an
+/// SSE stream, both of which are funneled into `tx`.
+#[derive(Clone)]
+struct Connection {
+    client: Client,
+    url: Url,
+    session_id: Arc<Mutex<Option<String>>>,
+    tx: UnboundedSender<ServerJsonRpcMessage>,
+    annotations: Annotations,
+}
+
+impl Connection {
+    fn session_id(&self) -> Option<String> {
+        self.session_id.lock().ok().and_then(|id| id.clone())
+    }
+
+    fn remember_session(&self, response: &Response) {
+        if let Some(id) = response
+            .headers()
+            .get(HEADER_SESSION_ID)
+            .and_then(|v| v.to_str().ok())
+        {
+            if let Ok(mut session_id) = self.session_id.lock() {
+                *session_id = Some(id.to_string());
+            }
+        }
+    }
+
+    async fn post(&self, message: &ClientJsonRpcMessage) -> Result<(), TransportError> {
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .header(ACCEPT, format!("{}, {}", JSON, EVENT_STREAM))
+            .json(&with_progress_token(message)?);
+
+        if let Some(id) = self.session_id() {
+            request = request.header(HEADER_SESSION_ID, id);
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        self.remember_session(&response);
+
+        if response.status() == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        if content_type.starts_with(EVENT_STREAM) {
+            self.forward(response).await
+        } else if content_type.starts_with(JSON) {
+            match self.annotations.parse::<Batch>(&response.bytes().await?)? {
+                Batch::One(message) => self.send(message),
+                Batch::Many(messages) => messages.into_iter().for_each(|m| self.send(m)),
+            }
+            Ok(())
+        } else if content_type.is_empty() {
+            Ok(())
+        } else {
+            Err(TransportError::UnexpectedContentType(content_type))
+        }
+    }
+
+    /// Opens the optional GET stream the server uses for requests and
+    /// notifications that aren't tied to one of our POSTs.
+    async fn listen(&self) -> Result<(), TransportError> {
+        let mut request = self.client.get(self.url.clone()).header(ACCEPT, EVENT_STREAM);
+
+        if let Some(id) = self.session_id() {
+            request = request.header(HEADER_SESSION_ID, id);
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            // Servers aren't required to offer a standalone stream.
+            return Ok(());
+        }
+
+        self.forward(response.error_for_status()?).await
+    }
+
+    /// Tells the server we're done with the session, if it issued one.
+    async fn terminate(&self) {
+        let Some(id) = self.session_id() else {
+            return;
+        };
+
+        let result = self
+            .client
+            .delete(self.url.clone())
+            .header(HEADER_SESSION_ID, id)
+            .send()
+            .await;
+
+        if let Err(e) = result {
+            log::warn!("Failed to terminate MCP session at {}: {}", self.url, e);
+        }
+    }
+
+    /// POST a message whose answer, if any, comes back on the event stream.
+    async fn deliver(&self, message: &ClientJsonRpcMessage) -> Result<(), TransportError> {
+        self.client
+            .post(self.url.clone())
+            .json(&with_progress_token(message)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn forward(&self, response: Response) -> Result<(), TransportError> {
+        self.forward_events(Box::pin(SseStream::from_byte_stream(response.bytes_stream())))
+            .await
+    }
+
+    async fn forward_events(&self, mut events: Events) -> Result<(), TransportError> {
+        while let Some(event) = events.next().await {
+            let event = event.map_err(|e| TransportError::Sse(format!("{:?}", e)))?;
+            let Some(data) = event.data else {
+                continue;
+            };
+
+            match self.annotations.parse(data.as_bytes()) {
+                Ok(message) => self.send(message),
+                Err(e) => log::warn!("Dropping malformed message from {}: {}", self.url, e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send(&self, message: ServerJsonRpcMessage) {
+        if self.tx.unbounded_send(message).is_err() {
+            log::debug!("Dropping message from {}, transport closed", self.url);
+        }
+    }
+
+    /// Surface a failure to send `message` to whoever is awaiting it, rather
+    /// than leaving them hanging forever.
+    fn fail(&self, message: ClientJsonRpcMessage, error: TransportError) {
+        log::error!("Failed to send message to {}: {}", self.url, error);
+
+        if let ClientJsonRpcMessage::Request(request) = message {
+            self.send(ServerJsonRpcMessage::Error(JsonRpcError {
+                jsonrpc: JsonRpcVersion2_0,
+                id: request.id,
+                error: ErrorData::internal_error(error.to_string(), None),
+            }));
+        }
+    }
+}
+
+/// Owns the background work of a connection, and cleans it up once rmcp
+/// drops the transport.
+struct Session {
+    connection: Connection,
+    listener: Option<tokio::task::AbortHandle>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let connection = self.connection.clone();
+            runtime.spawn(async move { connection.terminate().await });
+        }
+    }
+}
+
+/// Client side of the MCP Streamable HTTP transport.
+///
+/// Returns a sink/stream pair that rmcp can `serve` directly.
+pub fn streamable_http(
+    url: Url,
+    client: Client,
+    annotations: Annotations,
+) -> (
+    impl Sink<ClientJsonRpcMessage, Error = TransportError> + Send + 'static,
+    impl Stream<Item = ServerJsonRpcMessage> + Send + 'static,
+) {
+    let (tx, rx) = unbounded();
+
+    let session = Session {
+        connection: Connection {
+            client,
+            url,
+            session_id: Default::default(),
+            tx,
+            annotations,
+        },
+        listener: None,
+    };
+
+    let sink = futures::sink::unfold(session, |mut session, message: ClientJsonRpcMessage| async move {
+        let connection = session.connection.clone();
+
+        if is_initialized(&message) && session.listener.is_none() {
+            let listener = connection.clone();
+            let handle = tokio::spawn(async move {
+                if let Err(e) = listener.listen().await {
+                    log::warn!("MCP event stream at {} closed: {}", listener.url, e);
+                }
+            });
+            session.listener = Some(handle.abort_handle());
+        }
+
+        // Responses can take as long as the tool does, so don't hold up the
+        // rest of the service waiting for them.
+        tokio::spawn(async move {
+            if let Err(e) = connection.post(&message).await {
+                connection.fail(message, e);
+            }
+        });
+
+        Ok(session)
+    });
+
+    (sink, rx)
+}
+
+/// Client side of the older HTTP+SSE transport. Messages come in on one
+/// long-lived event stream, which starts by telling us where to POST ours.
+///
+/// rmcp has one of its own, but it sends messages as they are, without
+/// progress tokens.
+pub async fn sse(
+    url: Url,
+    client: Client,
+    annotations: Annotations,
+) -> Result<
+    (
+        impl Sink<ClientJsonRpcMessage, Error = TransportError> + Send + 'static,
+        impl Stream<Item = ServerJsonRpcMessage> + Send + 'static,
+    ),
+    TransportError,
+> {
+    let response = client
+        .get(url.clone())
+        .header(ACCEPT, EVENT_STREAM)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut events: Events = Box::pin(SseStream::from_byte_stream(response.bytes_stream()));
+
+    let endpoint = loop {
+        let event = events
+            .next()
+            .await
+            .ok_or_else(|| TransportError::Sse("Closed before sending an endpoint".to_string()))?
+            .map_err(|e| TransportError::Sse(format!("{:?}", e)))?;
+
+        if event.event.as_deref() == Some("endpoint") {
+            let endpoint = event.data.unwrap_or_default();
+            break url
+                .join(endpoint.trim())
+                .map_err(|e| TransportError::Sse(format!("Bad endpoint {}: {}", endpoint, e)))?;
+        }
+    };
+
+    let (tx, rx) = unbounded();
+
+    let connection = Connection {
+        client,
+        url: endpoint,
+        session_id: Default::default(),
+        tx,
+        annotations,
+    };
+
+    let listener = connection.clone();
+    let handle = tokio::spawn(async move {
+        if let Err(e) = listener.forward_events(events).await {
+            log::warn!("MCP event stream at {} closed: {}", url, e);
+        }
+    });
+
+    let session = Session {
+        connection,
+        listener: Some(handle.abort_handle()),
+    };
+
+    let sink = futures::sink::unfold(session, |session, message: ClientJsonRpcMessage| async move {
+        let connection = session.connection.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = connection.deliver(&message).await {
+                connection.fail(message, e);
+            }
+        });
+
+        Ok(session)
+    });
+
+    Ok((sink, rx))
+}
+
+/// Read newline-delimited messages from a local server.
+pub fn from_lines<R: AsyncRead + Send + Unpin + 'static>(
+    reader: R,
+    annotations: Annotations,
+) -> impl Stream<Item = ServerJsonRpcMessage> + Send + 'static {
+    let lines = BufReader::new(reader).lines();
+
+    futures::stream::unfold(lines, move |mut lines| {
+        let annotations = annotations.clone();
+        async move {
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => match annotations.parse(line.as_bytes()) {
+                        Ok(message) => return Some((message, lines)),
+                        Err(e) => log::warn!("Dropping malformed message: {}", e),
+                    },
+                    Ok(None) => return None,
+                    Err(e) => {
+                        log::error!("Failed to read from MCP server: {}", e);
+                        return None;
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn is_initialized(message: &ClientJsonRpcMessage) -> bool {
+    matches!(
+        message,
+        ClientJsonRpcMessage::Notification(JsonRpcNotification {
+            notification: ClientNotification::InitializedNotification(_),
+            ..
+        })
+    )
+}
+
+/// Serialize an outgoing message, asking for progress on tool calls.
+///
+/// rmcp has no way to set a request's `_meta`, so we add the progress token on
+/// the way out. It's the request's own id, which is already unique for the
+/// connection, and lets us match progress back up with the call it's for.
+pub fn with_progress_token(message: &ClientJsonRpcMessage) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(message)?;
+
+    if value["method"] == "tools/call" {
+        let id = value["id"].clone();
+        if let Some(params) = value["params"].as_object_mut() {
+            params.insert("_meta".to_string(), json!({ "progressToken": id }));
+        }
+    }
+
+    Ok(value)
+}
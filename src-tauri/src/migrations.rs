@@ -509,6 +509,90 @@ END;
 INSERT INTO settings (display, key, value, type)
 SELECT 'Labs Mode', 'labs-mode', '"false"', 'select'
 WHERE NOT EXISTS (SELECT 1 FROM settings WHERE key = 'labs-mode');
+"#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 20,
+            description: "add_remote_mcp_servers",
+            sql: r#"
+ALTER TABLE mcp_servers ADD COLUMN transport TEXT NOT NULL DEFAULT "stdio";
+ALTER TABLE mcp_servers ADD COLUMN url TEXT;
+ALTER TABLE mcp_servers ADD COLUMN headers JSON NOT NULL DEFAULT "{}";
+ALTER TABLE mcp_servers ADD COLUMN bearer_token TEXT;
+"#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 21,
+            description: "add_mcp_server_options",
+            sql: r#"
+ALTER TABLE mcp_servers ADD COLUMN options JSON NOT NULL DEFAULT "{}";
+"#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 22,
+            description: "add_mcp_sampling_requests",
+            sql: r#"
+CREATE TABLE IF NOT EXISTS mcp_sampling_requests (
+    id              INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    session_id      INTEGER NOT NULL,
+    server          TEXT NOT NULL,
+    tool_call_id    TEXT,
+    engine_id       INTEGER,
+    model           TEXT,
+    request         JSON NOT NULL DEFAULT "{}",
+    response        TEXT,
+    status          TEXT NOT NULL,
+    tokens          INTEGER NOT NULL DEFAULT 0,
+    created         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified        TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+"#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 23,
+            description: "add_mcp_roots",
+            sql: r#"
+CREATE TABLE IF NOT EXISTS roots (
+    id              INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    app_id          INTEGER,
+    session_id      INTEGER,
+    path            TEXT NOT NULL,
+    name            TEXT,
+    created         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified        TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(app_id) REFERENCES apps(id) ON DELETE CASCADE,
+    FOREIGN KEY(session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+"#,
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 24,
+            description: "add_tool_calls",
+            sql: r#"
+-- An audit log, so rows outlive the sessions they belong to.
+CREATE TABLE IF NOT EXISTS tool_calls (
+    id              INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    session_id      INTEGER NOT NULL,
+    app_run_id      INTEGER,
+    request_id      TEXT,
+    server          TEXT,
+    tool            TEXT NOT NULL,
+    arguments       JSON NOT NULL DEFAULT "{}",
+    result          JSON,
+    error           TEXT,
+    duration_ms     INTEGER NOT NULL,
+    started         TIMESTAMP NOT NULL,
+    finished        TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS tool_calls_session_id ON tool_calls(session_id);
+CREATE INDEX IF NOT EXISTS tool_calls_started ON tool_calls(started);
 "#,
             kind: MigrationKind::Up,
         },
@@ -1,7 +1,4 @@
-use std::collections::HashSet;
-
-use anyhow::{anyhow, Result};
-use sysinfo::{Pid, System};
+use sysinfo::Pid;
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct Process {
@@ -9,41 +6,15 @@ pub struct Process {
 }
 
 impl Process {
-    pub fn current() -> Result<Self> {
-        sysinfo::get_current_pid()
-            .map(|pid| Self { pid })
-            .map_err(|e| anyhow!("Failed to get current process ID: {}", e))
-    }
-
-    pub fn children(&self, sys: &System) -> HashSet<Self> {
-        let mut procs = HashSet::new();
-        for (pid, proc) in sys.processes().iter() {
-            if let Some(parent_pid) = proc.parent() {
-                if parent_pid == self.pid {
-                    let child_proc = Self { pid: *pid };
-                    procs.insert(child_proc.clone());
-                    procs.extend(child_proc.children(sys));
-                }
-            }
-        }
-        procs
+    /// Send `signal` to every process in the group this process leads,
+    /// returning whether there were any left.
+    pub fn signal_group(&self, signal: i32) -> bool {
+        // SAFETY: killpg only sends a signal.
+        unsafe { libc::killpg(self.pid.as_u32() as i32, signal) == 0 }
     }
 
-    pub fn kill_tree(&self) -> Result<bool> {
-        let mut sys = System::new();
-        sys.refresh_all();
-
-        let children = self.children(&sys);
-        for proc in children {
-            if let Some(p) = sys.process(proc.pid) {
-                p.kill();
-            }
-        }
-
-        if let Some(parent) = sys.process(self.pid) {
-            Ok(parent.kill())
-        } else {
-            Ok(false)
-        }
+    /// Whether anything's left of the group this process leads.
+    pub fn group_alive(&self) -> bool {
+        self.signal_group(0)
     }
 }
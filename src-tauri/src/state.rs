@@ -1,21 +1,205 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::{daemon::Watchers, mcp::server::McpServer};
+use crate::{
+    daemon::Watchers, mcp::audit::Audit, mcp::metadata::MetadataCache, mcp::metrics::Metrics,
+    mcp::orphans::Groups, mcp::policy::Policy, mcp::pool::Pool, mcp::sampling::SamplingResponse,
+    mcp::server::McpServer,
+};
 
-use tokio::sync::Mutex;
+use anyhow::{anyhow, Result};
+use rmcp::model::{Root, Tool};
+use tokio::sync::{oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
 
 type SessionId = i32;
 type McpServerName = String;
 type ToolName = String;
+type RequestId = String;
+
+/// Joins a server's name to each of its tools' names, so that tools of the same
+/// name on different servers don't clash.
+const SEPARATOR: &str = "__";
+
+/// Where a qualified tool name leads.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub server: McpServerName,
+    pub tool: ToolName,
+}
 
 #[derive(Debug, Default)]
 pub struct RunningSession {
-    pub mcp_servers: HashMap<McpServerName, McpServer>,
-    pub tools: HashMap<ToolName, McpServerName>,
+    pub mcp_servers: HashMap<McpServerName, Arc<McpServer>>,
+    pub tools: HashMap<ToolName, Route>,
+    /// Directories the session's servers may work in, offered to them via
+    /// `roots/list`.
+    pub roots: Vec<Root>,
+    /// Servers on their way up, which have yet to say what they're called.
+    pub starting: HashMap<u64, Starting>,
+}
+
+/// A server being started, known only by what it runs until it's up.
+#[derive(Debug)]
+pub struct Starting {
+    pub label: String,
+    pub since: Instant,
+}
+
+/// The longest function name engines accept.
+const MAX_QUALIFIED: usize = 64;
+
+/// The name `tool` on `server` is exposed as. Engines only accept a narrow set
+/// of characters in function names, and no more than 64 of them, so anything
+/// else is replaced, and long names are cut short. Tool names that had to be
+/// changed get a hash of the original, so that they can't clash with one
+/// another.
+pub fn qualify(server: &str, tool: &str) -> String {
+    let sanitize = |name: &str| -> String {
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+
+    let sanitized = sanitize(tool);
+    let qualified = format!("{}{}{}", sanitize(server), SEPARATOR, sanitized);
+    if qualified.len() <= MAX_QUALIFIED && sanitized == tool {
+        return qualified;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    (server, tool).hash(&mut hasher);
+    let suffix = format!("_{:08x}", hasher.finish() as u32);
+
+    // Only ASCII is left, so any length is a char boundary.
+    let kept = qualified.len().min(MAX_QUALIFIED - suffix.len());
+    format!("{}{}", &qualified[..kept], suffix)
+}
+
+impl RunningSession {
+    /// Add a server, and route its tools to it. Fails if any of them clash
+    /// with another server's, leaving it to the caller to let go of the
+    /// server.
+    pub fn register(&mut self, name: McpServerName, server: &Arc<McpServer>, tools: Vec<Tool>) -> Result<()> {
+        self.route(&name, &tools)?;
+        self.mcp_servers.insert(name, server.clone());
+        Ok(())
+    }
+
+    /// Route `tools` to server `name`, replacing whatever it offered before.
+    /// Fails without changing anything if a qualified name is already taken
+    /// by another server.
+    pub fn route(&mut self, name: &str, tools: &[Tool]) -> Result<()> {
+        let conflicts: Vec<String> = tools
+            .iter()
+            .map(|tool| qualify(name, &tool.name))
+            .filter(|qualified| {
+                self.tools
+                    .get(qualified)
+                    .is_some_and(|route| route.server != name)
+            })
+            .collect();
+
+        if !conflicts.is_empty() {
+            return Err(anyhow!(
+                "Tools of '{}' clash with those of another server: {}",
+                name,
+                conflicts.join(", ")
+            ));
+        }
+
+        self.tools.retain(|_, route| route.server != name);
+        for tool in tools {
+            self.tools.insert(
+                qualify(name, &tool.name),
+                Route {
+                    server: name.to_string(),
+                    tool: tool.name.to_string(),
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Find where a tool call should go. Bare tool names are accepted too, as
+    /// long as only one server offers a tool by that name.
+    pub fn resolve(&self, name: &str) -> Result<&Route> {
+        if let Some(route) = self.tools.get(name) {
+            return Ok(route);
+        }
+
+        let routes: Vec<&Route> = self.tools.values().filter(|route| route.tool == name).collect();
+        match routes.as_slice() {
+            [route] => Ok(route),
+            [] => Err(anyhow!("Tool '{}' not found", name)),
+            _ => Err(anyhow!(
+                "Tool '{}' is offered by several servers ({}), use a qualified name",
+                name,
+                routes.iter().map(|route| route.server.as_str()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    /// List a server as starting, until `started` is called with the id this
+    /// returns.
+    pub fn starting(&mut self, label: String) -> u64 {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT.fetch_add(1, Ordering::SeqCst);
+        self.starting.insert(
+            id,
+            Starting {
+                label,
+                since: Instant::now(),
+            },
+        );
+        id
+    }
+
+    pub fn started(&mut self, id: u64) {
+        self.starting.remove(&id);
+    }
+
+    /// Remove a server, along with any tools routed to it.
+    pub fn deregister(&mut self, name: &str) -> Option<Arc<McpServer>> {
+        self.tools.retain(|_, route| route.server != name);
+        self.mcp_servers.remove(name)
+    }
 }
 
 #[derive(Debug)]
 pub struct State {
     pub sessions: Mutex<HashMap<SessionId, RunningSession>>,
     pub watchers: Mutex<Watchers>,
+    /// In-flight tool calls, so they can be cancelled from the frontend.
+    pub tool_calls: Mutex<HashMap<RequestId, CancellationToken>>,
+    /// Sampling requests waiting on the frontend.
+    pub sampling: Mutex<HashMap<RequestId, oneshot::Sender<SamplingResponse>>>,
+    /// Which tool calls may go ahead.
+    pub policy: Mutex<Policy>,
+    /// Tool calls waiting on the user's approval.
+    pub tool_approvals: Mutex<HashMap<RequestId, oneshot::Sender<bool>>>,
+    /// Where every tool call is recorded.
+    pub audit: Audit,
+    /// Counts and latencies of tool calls, by server and tool.
+    pub metrics: Metrics,
+    /// Stops the Prometheus endpoint, when it's running.
+    pub metrics_endpoint: Mutex<Option<CancellationToken>>,
+    /// What servers have said about themselves, so that they needn't be
+    /// started just to ask again.
+    pub metadata: MetadataCache,
+    pub pool: Pool,
+    /// The process groups of running servers, written down in case we crash.
+    pub groups: Groups,
 }
@@ -2,13 +2,23 @@ import { listen } from '$events/registry';
 import type { Event, FileCreatedEvent, FileUpdatedEvent } from '$events/types';
 import { info } from '$lib/logger';
 import { Trigger } from '$lib/models';
+import type { FilesystemConfig } from '$lib/models/trigger.svelte';
+
+// The watched folder is made a root of the run, so that the app's MCP servers
+// can get at the files that set it off.
+//
+async function execute(id: number, payload: object) {
+    const trigger = Trigger.find(id);
+    const { path } = trigger.config as FilesystemConfig;
+    await trigger.app.execute(payload, [path]);
+}
 
 listen('filesystem/created', async (event: Event<FileCreatedEvent>) => {
     info('→ filesystem/created');
-    await Trigger.find(event.payload.id).app.execute(event.payload);
+    await execute(event.payload.id, event.payload);
 });
 
 listen('filesystem/updated', async (event: Event<FileUpdatedEvent>) => {
     info('→ filesystem/updated');
-    await Trigger.find(event.payload.id).app.execute(event.payload);
+    await execute(event.payload.id, event.payload);
 });
@@ -9,4 +9,6 @@ export async function listen() {
     await invoke('unwatch_all');
     await import('./filesystem');
     await import('./mcp');
+    await import('./policy');
+    await import('./sampling');
 }
@@ -0,0 +1,22 @@
+import type { Event } from '@tauri-apps/api/event';
+
+import { listen } from '$events/registry';
+import {
+    type McpToolApprovalCancelledEvent,
+    type McpToolApprovalEvent,
+    respondToMcpToolApproval,
+} from '$lib/mcp';
+import { toolApprovals } from '$lib/stores/policy';
+
+listen('mcp/tool-approval', async (event: Event<McpToolApprovalEvent>) => {
+    const approval = await toolApprovals.ask(event.payload);
+
+    // Cancelled calls have nobody left to answer.
+    if (approval != 'cancelled') {
+        await respondToMcpToolApproval(event.payload.id, approval == 'approved');
+    }
+});
+
+listen('mcp/tool-approval-cancelled', (event: Event<McpToolApprovalCancelledEvent>) => {
+    toolApprovals.cancel(event.payload.id);
+});
@@ -0,0 +1,14 @@
+import type { Event } from '@tauri-apps/api/event';
+
+import { listen } from '$events/registry';
+import type { McpSamplingCancelledEvent, McpSamplingEvent } from '$lib/mcp';
+import { sample } from '$lib/sampling';
+import { approvals } from '$lib/stores/sampling';
+
+listen('mcp/sampling', async (event: Event<McpSamplingEvent>) => {
+    await sample(event.payload);
+});
+
+listen('mcp/sampling-cancelled', (event: Event<McpSamplingCancelledEvent>) => {
+    approvals.cancel(event.payload.id);
+});
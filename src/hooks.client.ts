@@ -13,9 +13,11 @@ import * as apps from '$lib/apps';
 import { setupDeeplinks } from '$lib/deeplinks';
 import { error } from '$lib/logger';
 import { info } from '$lib/logger';
+import { serveMcpMetrics } from '$lib/mcp';
 import { resync } from '$lib/models';
 import Config from '$lib/models/config.svelte';
 import Engine from '$lib/models/engine.svelte';
+import * as policy from '$lib/policy';
 import { startActiveRelays } from '$lib/relays';
 import startup, { StartupCheck } from '$lib/startup';
 import { isUpToDate } from '$lib/updates';
@@ -33,6 +35,13 @@ export const init: ClientInit = async () => {
     await resync();
     info('[green]✔ database synced');
 
+    await policy.load();
+    info('[green]✔ tool policy loaded');
+
+    if (Config.mcpMetricsPort) {
+        await serveMcpMetrics(Config.mcpMetricsPort).catch(e => error('MetricsError', e));
+    }
+
     await startActiveRelays();
 
     spawn(new Scheduler());
@@ -2,7 +2,7 @@ import { invoke } from '@tauri-apps/api/core';
 
 import { dispatch } from '$lib/dispatch';
 import { info } from '$lib/logger';
-import { App, AppRun, AppStep, Session, Trigger } from '$lib/models';
+import { App, AppRun, AppStep, Root, Session, Trigger } from '$lib/models';
 import type { FilesystemConfig } from '$lib/models/trigger.svelte';
 
 /**
@@ -37,8 +37,9 @@ export async function initializeBackendWatchers() {
  *
  * @param app App to run
  * @param [input=undefined] Input data formatted as a message for an LLM
+ * @param [roots=[]] Extra directories MCP servers may work in, for this run only
  */
-export async function execute(app: App, input?: object): Promise<AppRun> {
+export async function execute(app: App, input?: object, roots: string[] = []): Promise<AppRun> {
     info(`executing app: ${app.name}`);
 
     const session = await Session.create({
@@ -51,6 +52,8 @@ export async function execute(app: App, input?: object): Promise<AppRun> {
         },
     });
 
+    await roots.awaitAll(async path => await Root.create({ sessionId: session.id, path }));
+
     if (input) {
         session.addMessage({
             role: 'system',
@@ -2,6 +2,7 @@ import { invoke } from '@tauri-apps/api/core';
 import uuid4 from 'uuid4';
 
 import type { Options } from '$lib/engines/types';
+import type { McpToolResult } from '$lib/mcp';
 import { error } from '$lib/logger';
 import { App, Engine, Message, Model, Session } from '$lib/models';
 
@@ -41,19 +42,29 @@ export async function dispatch(session: Session, model: Model, prompt?: string):
     );
 
     if (message.toolCalls?.length) {
+        // Some engines, like Ollama, don't give tool calls a unique
+        // identifier. In those cases, do it ourselves, so that future calls
+        // to engines that do (like OpenAI), don't explode because they expect
+        // one to be set.
         for (const call of message.toolCalls) {
-            // Some engines, like Ollama, don't give tool calls a unique
-            // identifier. In those cases, do it ourselves, so that future
-            // calls to engines that do (like OpenAI), don't explode because
-            // they expect one to be set.
             call.id ||= uuid4();
+        }
 
-            const content: string = await invoke('call_mcp_tool', {
-                sessionId: session.id,
-                name: call.function.name,
-                arguments: call.function.arguments,
-            });
+        // Calls are independent of each other, so run them all at once. A
+        // call that's denied, rejected, or fails outright shouldn't end the
+        // turn, so settle them all and report failures back to the model.
+        const results = await Promise.allSettled(
+            message.toolCalls.map(call =>
+                invoke<McpToolResult>('call_mcp_tool', {
+                    sessionId: session.id,
+                    name: call.function.name,
+                    arguments: call.function.arguments,
+                    requestId: call.id,
+                })
+            )
+        );
 
+        for (const [i, call] of message.toolCalls.entries()) {
             await session.addMessage({
                 role: 'assistant',
                 content: '',
@@ -62,6 +73,16 @@ export async function dispatch(session: Session, model: Model, prompt?: string):
                 toolCalls: [call],
             });
 
+            const result = results[i];
+            let content: string;
+
+            if (result.status == 'fulfilled') {
+                content = result.value.text;
+            } else {
+                error('ToolCallError', call.function.name, result.reason);
+                content = String(result.reason);
+            }
+
             await session.addMessage({
                 role: 'tool',
                 content,
@@ -69,9 +90,9 @@ export async function dispatch(session: Session, model: Model, prompt?: string):
                 model: model.id,
                 toolCallId: call.id,
             });
-
-            return await dispatch(session, model);
         }
+
+        return await dispatch(session, model);
     }
 
     message.engineId = model.engineId;
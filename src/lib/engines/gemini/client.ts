@@ -33,6 +33,8 @@ export default class Gemini implements Client {
 
         const config: GenerateContentConfig = {
             temperature: options?.temperature,
+            maxOutputTokens: options?.num_predict,
+            stopSequences: options?.stop,
         };
 
         // Add system instruction if system messages exist
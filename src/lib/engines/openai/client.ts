@@ -26,12 +26,14 @@ export default class OpenAI implements Client {
         model: Model,
         history: Message[],
         tools: Tool[] = [],
-        _options: Options = {}
+        options: Options = {}
     ): Promise<Message> {
         const messages = history.map(m => OpenAiMessage.from(m));
         const completion: ChatCompletionCreateParamsNonStreaming = {
             model: model.name,
             messages,
+            max_tokens: options.num_predict,
+            stop: options.stop,
         };
 
         if (tools.length > 0) {
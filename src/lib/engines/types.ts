@@ -23,6 +23,9 @@ export interface ClientProps extends ClientOptions {
 
 export interface Options {
     num_ctx?: number;
+    // The most tokens to generate
+    num_predict?: number;
+    stop?: string[];
     temperature?: number;
 }
 
@@ -1,11 +1,86 @@
 import { invoke } from '@tauri-apps/api/core';
 
 import type { Tool } from '$lib/engines/types';
+import type { Root } from '$lib/models';
 
-export interface McpConfig {
+export type McpTransport = 'stdio' | 'sse' | 'streamable_http';
+
+export interface McpRestartPolicy {
+    maxRestarts?: number;
+    initialBackoffMs?: number;
+    maxBackoffMs?: number;
+}
+
+export interface McpContainer {
+    // `docker`, `podman`, or a path to either
+    runtime?: string;
+    image: string;
+    // `host:container[:options]`
+    volumes?: string[];
+    // Variables to pass through from our own environment
+    passEnv?: string[];
+    network?: string;
+}
+
+export interface McpSandbox {
+    // Paths the server may read, on top of the system's own
+    read?: string[];
+    // Paths the server may read and write
+    write?: string[];
+    noNetwork?: boolean;
+    // Kill the server on system calls it has no business making
+    seccomp?: boolean;
+}
+
+export interface McpLimits {
+    memoryMb?: number;
+    // Total CPU time, rather than a share of the CPU
+    cpuSeconds?: number;
+    openFiles?: number;
+}
+
+export interface McpSampling {
+    // Engine and model to sample with, instead of the session's
+    engineId?: number;
+    model?: string;
+    // Tokens the server may spend per session, charged by the `maxTokens`
+    // each request asks for
+    budget?: number;
+    // Sample without asking first
+    autoApprove?: boolean;
+}
+
+// Less common, per-server settings. Stored as JSON alongside the server and
+// merged into its `McpConfig` when launching.
+//
+export interface McpServerOptions {
+    // Working directory of local servers
+    cwd?: string;
+    // Run the server in a container, passing `args` to the image
+    container?: McpContainer;
+    // Confine a local server to the paths it's been given
+    sandbox?: McpSandbox;
+    limits?: McpLimits;
+    // Let the server ask for completions of its own
+    sampling?: McpSampling;
+    restart?: McpRestartPolicy;
+    // Give up on tool calls that run longer than this
+    timeoutMs?: number;
+    // How long a local server has to exit once asked to, before it's killed
+    shutdownGraceMs?: number;
+    // Run a separate instance of the server for each session, instead of
+    // sharing one, for servers that keep per-session state
+    isolated?: boolean;
+}
+
+export interface McpConfig extends McpServerOptions {
+    transport?: McpTransport;
     command: string;
     args: string[];
     env: Record<string, string>;
+    url?: string;
+    headers?: Record<string, string>;
+    bearerToken?: string;
 }
 
 export interface McpTool {
@@ -14,6 +89,214 @@ export interface McpTool {
     inputSchema: McpInputSchema;
 }
 
+export type McpToolContent =
+    | { type: 'text'; text: string }
+    | { type: 'image'; data: string; mimeType: string }
+    | { type: 'resource'; uri: string; mimeType?: string; text?: string; blob?: string };
+
+export interface McpToolResult {
+    content: McpToolContent[];
+    isError: boolean;
+    // Plain text rendering of `content`, for engines that only handle text
+    text: string;
+}
+
+export interface McpPromptArgument {
+    name: string;
+    description?: string;
+    required?: boolean;
+}
+
+export interface McpPrompt {
+    server: string;
+    name: string;
+    description?: string;
+    arguments?: McpPromptArgument[];
+}
+
+export interface McpPromptMessage {
+    role: 'user' | 'assistant';
+    content: McpToolContent;
+    // Plain text rendering of `content`, suitable for a `Message`'s content
+    text: string;
+}
+
+export interface McpPromptResult {
+    description?: string;
+    messages: McpPromptMessage[];
+}
+
+export type McpLogLevel =
+    | 'debug'
+    | 'info'
+    | 'notice'
+    | 'warning'
+    | 'error'
+    | 'critical'
+    | 'alert'
+    | 'emergency';
+
+// Payload of `mcp/tools-changed` events, sent when a server's tools change
+// while it's running.
+//
+export interface McpToolsChangedEvent {
+    sessionId: number;
+    server: string;
+    tools: McpTool[];
+}
+
+export type McpServerStatus = 'starting' | 'ready' | 'degraded' | 'dead';
+
+// Payload of `mcp/server-status` events, sent whenever a server's status
+// changes: when it's attached to a session, stops or starts answering pings,
+// or exits.
+//
+export interface McpServerStatusEvent {
+    sessionId: number;
+    server: string;
+    status: McpServerStatus;
+    previous: McpServerStatus;
+    error?: string;
+}
+
+// A server as it's running in a session.
+//
+export interface McpRunningServer {
+    sessionId: number;
+    // What the server runs, while it's still starting
+    name: string;
+    status: McpServerStatus;
+    // Only local servers have one
+    pid?: number;
+    uptimeMs: number;
+    lastError?: string;
+    toolCount: number;
+}
+
+// Payload of `mcp/progress` events.
+//
+export interface McpProgressEvent {
+    sessionId: number;
+    server: string;
+    toolCallId?: string;
+    progress: number;
+    total?: number;
+}
+
+// Payload of `mcp/message` events, ie. log messages sent over MCP.
+//
+export interface McpMessageEvent {
+    sessionId: number;
+    server: string;
+    toolCallId?: string;
+    level: McpLogLevel;
+    logger?: string;
+    data: unknown;
+}
+
+export interface McpSamplingMessage {
+    role: 'user' | 'assistant';
+    content: McpToolContent;
+}
+
+// What a server asks for in a `sampling/createMessage` request.
+//
+export interface McpSamplingParams {
+    messages: McpSamplingMessage[];
+    systemPrompt?: string;
+    temperature?: number;
+    maxTokens: number;
+    stopSequences?: string[];
+    modelPreferences?: McpModelPreferences;
+}
+
+// Which models a server would rather sample with. Hints are matched against
+// model names, in order.
+//
+export interface McpModelPreferences {
+    hints?: { name?: string }[];
+    costPriority?: number;
+    speedPriority?: number;
+    intelligencePriority?: number;
+}
+
+// Payload of `mcp/sampling` events, sent when a server wants a completion.
+// Answer with `respondToMcpSampling`.
+//
+export interface McpSamplingEvent {
+    sessionId: number;
+    server: string;
+    id: string;
+    toolCallId?: string;
+    sampling: McpSampling;
+    request: McpSamplingParams;
+}
+
+// Payload of `mcp/sampling-cancelled` events, sent when a server gives up on
+// a sampling request before we've answered it.
+//
+export interface McpSamplingCancelledEvent {
+    sessionId: number;
+    server: string;
+    id: string;
+}
+
+export interface McpSamplingResult {
+    model: string;
+    stopReason?: string;
+    role: 'assistant';
+    content: McpToolContent;
+}
+
+export type McpSamplingResponse = { result: McpSamplingResult } | { error: string };
+
+// Hints a server gives about what a tool does. Servers needn't be truthful.
+//
+export interface McpToolAnnotations {
+    title?: string;
+    readOnlyHint?: boolean;
+    destructiveHint?: boolean;
+    idempotentHint?: boolean;
+    openWorldHint?: boolean;
+}
+
+export type McpPolicyAction = 'allow' | 'deny' | 'ask';
+
+// A rule deciding what happens to tool calls. Rules are checked in order, and
+// the first to match decides; calls no rule matches are allowed.
+//
+export interface McpPolicyRule {
+    // Globs on the server's and the tool's names, `*` if left out
+    server?: string;
+    tool?: string;
+    // Match on the tool's annotations. Tools are taken to be destructive,
+    // and not read-only, unless they say otherwise.
+    destructive?: boolean;
+    readOnly?: boolean;
+    // Regexes arguments must match, by name
+    arguments?: Record<string, string>;
+    action: McpPolicyAction;
+}
+
+// Payload of `mcp/tool-approval` events, sent when the policy holds a tool
+// call for the user to approve. Answer with `respondToMcpToolApproval`.
+//
+export interface McpToolApprovalEvent {
+    id: string;
+    sessionId: number;
+    server: string;
+    tool: string;
+    arguments: Record<string, unknown>;
+    annotations: McpToolAnnotations;
+}
+
+// Payload of `mcp/tool-approval-cancelled` events, sent when a held call is
+// cancelled before the user has answered.
+//
+export interface McpToolApprovalCancelledEvent {
+    id: string;
+}
+
 export interface McpInputSchema {
     type: string;
     title: string;
@@ -24,6 +307,9 @@ export interface McpInputSchema {
 // Retrieve, and transform, tools from the MCP server, into `tools` object we
 // can send to the LLM.
 //
+// Tool names are qualified with the server they belong to, as in
+// `server__tool`, so that servers offering tools of the same name don't clash.
+//
 export async function getMcpTools(sessionId: number): Promise<Tool[]> {
     return (await invoke<McpTool[]>('get_mcp_tools', { sessionId })).map(tool => {
         return {
@@ -40,3 +326,160 @@ export async function getMcpTools(sessionId: number): Promise<Tool[]> {
         };
     });
 }
+
+// Every prompt offered by the MCP servers running in a session.
+//
+export async function getMcpPrompts(sessionId: number): Promise<McpPrompt[]> {
+    return await invoke<McpPrompt[]>('list_mcp_prompts', { sessionId });
+}
+
+// Render a prompt into messages that can be added to a session.
+//
+export async function getMcpPrompt(
+    sessionId: number,
+    server: string,
+    name: string,
+    args: Record<string, string> = {}
+): Promise<McpPromptResult> {
+    return await invoke<McpPromptResult>('get_mcp_prompt', {
+        sessionId,
+        server,
+        name,
+        arguments: args,
+    });
+}
+
+// Every server running in every session, with how it's doing. Servers shared
+// between sessions are listed once for each.
+//
+export async function listRunningServers(): Promise<McpRunningServer[]> {
+    return await invoke<McpRunningServer[]>('list_running_servers');
+}
+
+// Offer `roots` to the MCP servers running in a session, replacing what
+// they were offered before. Set them before starting servers, so that they're
+// there from the outset.
+//
+export async function setMcpRoots(sessionId: number, roots: Root[]) {
+    await invoke('set_mcp_roots', {
+        sessionId,
+        roots: roots.map(root => ({ uri: root.uri, name: root.name })),
+    });
+}
+
+// Abort an in-flight tool call, by the `requestId` it was made with. The call
+// itself rejects with a `CancelledError`.
+//
+export async function cancelMcpToolCall(requestId: string) {
+    await invoke('cancel_mcp_tool_call', { requestId });
+}
+
+// Ask a server to only send log messages at `level` and above.
+//
+export async function setMcpLogLevel(sessionId: number, server: string, level: McpLogLevel) {
+    await invoke('set_mcp_log_level', { sessionId, server, level });
+}
+
+// Answer a server's sampling request, with either a completion or the reason
+// there isn't one.
+//
+export async function respondToMcpSampling(id: string, response: McpSamplingResponse) {
+    await invoke('respond_mcp_sampling', { id, response });
+}
+
+// Replace the rules tool calls are checked against. Fails, leaving the old
+// rules in place, if any glob or pattern doesn't compile.
+//
+export async function setMcpPolicy(rules: McpPolicyRule[]) {
+    await invoke('set_mcp_policy', { rules });
+}
+
+// Let a tool call held by the policy go ahead, or not.
+//
+export async function respondToMcpToolApproval(id: string, approved: boolean) {
+    await invoke('respond_mcp_tool_approval', { id, approved });
+}
+
+// A tool call, as recorded in the audit log.
+//
+export interface McpToolCallRecord {
+    id: number;
+    sessionId: number;
+    appRunId?: number;
+    requestId?: string;
+    // Missing when the tool couldn't be found
+    server?: string;
+    tool: string;
+    arguments: Record<string, unknown>;
+    result?: McpToolResult;
+    error?: string;
+    durationMs: number;
+    started: string;
+    finished: string;
+}
+
+// Which recorded tool calls to fetch. Anything left out matches everything.
+//
+export interface McpToolCallFilter {
+    sessionId?: number;
+    appRunId?: number;
+    server?: string;
+    tool?: string;
+    // Only calls that failed, or only those that didn't
+    failed?: boolean;
+    // Bounds on when calls started, as understood by SQLite's `datetime`
+    since?: string;
+    until?: string;
+    limit?: number;
+    offset?: number;
+}
+
+// Recorded tool calls matching `filter`, most recent first.
+//
+export async function queryMcpToolCalls(
+    filter: McpToolCallFilter = {}
+): Promise<McpToolCallRecord[]> {
+    return await invoke<McpToolCallRecord[]>('query_mcp_tool_calls', { filter });
+}
+
+// Write recorded tool calls matching `filter` to `path` as JSON Lines, oldest
+// first. Resolves to how many were written.
+//
+export async function exportMcpToolCalls(
+    path: string,
+    filter: McpToolCallFilter = {}
+): Promise<number> {
+    return await invoke<number>('export_mcp_tool_calls', { filter, path });
+}
+
+export interface McpLatencyBucket {
+    // Upper bound, or null for calls slower than every other bucket
+    leMs: number | null;
+    count: number;
+}
+
+// How a server's tool has fared since the app started, by server version.
+//
+export interface McpToolMetrics {
+    server: string;
+    version: string;
+    tool: string;
+    calls: number;
+    errors: number;
+    timeouts: number;
+    cancelled: number;
+    meanMs: number;
+    // Cumulative, so each bucket counts the calls in those below it too
+    buckets: McpLatencyBucket[];
+}
+
+export async function getMcpMetrics(): Promise<McpToolMetrics[]> {
+    return await invoke<McpToolMetrics[]>('get_mcp_metrics');
+}
+
+// Serve metrics for Prometheus on `http://localhost:<port>/metrics`, or stop
+// serving them if `port` is left out.
+//
+export async function serveMcpMetrics(port?: number) {
+    await invoke('serve_mcp_metrics', { port });
+}
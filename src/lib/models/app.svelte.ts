@@ -1,7 +1,7 @@
 import moment from 'moment';
 
 import { execute } from '$lib/apps';
-import { AppMcpServer, AppRun, AppStep, McpServer, Trigger } from '$lib/models';
+import { AppMcpServer, AppRun, AppStep, McpServer, Root, Trigger } from '$lib/models';
 import Base, { type ToSqlRow } from '$lib/models/base.svelte';
 
 const CHAT_APP_ID = 1;
@@ -82,6 +82,11 @@ export default class App extends Base<Row>('apps') {
         return Trigger.findBy({ appId: this.id }) || Trigger.new({ appId: this.id });
     }
 
+    // Directories MCP servers may work in, in every session the app runs.
+    get roots(): Root[] {
+        return Root.where({ appId: this.id });
+    }
+
     get runs(): AppRun[] {
         return AppRun.where({ appId: this.id }).sortBy('created').reverse();
     }
@@ -90,8 +95,26 @@ export default class App extends Base<Row>('apps') {
         return this.runs[0];
     }
 
-    async execute(input?: object): Promise<AppRun> {
-        return await execute(this, input);
+    async execute(input?: object, roots: string[] = []): Promise<AppRun> {
+        return await execute(this, input, roots);
+    }
+
+    async addRoot(path: string, name?: string): Promise<Root> {
+        const root = await Root.create({ appId: this.id, path, name });
+        await this.syncRoots();
+        return root;
+    }
+
+    async removeRoot(root: Root) {
+        await root.delete();
+        await this.syncRoots();
+    }
+
+    // Send the app's roots to the MCP servers of its runs in progress.
+    async syncRoots() {
+        await this.runs
+            .filter(run => run.isPending())
+            .awaitAll(async run => await run.session?.syncRoots());
     }
 
     async addStep(step: AppStep) {
@@ -1,3 +1,4 @@
+import type { McpPolicyRule } from '$lib/mcp';
 import Base, { type ToSqlRow } from '$lib/models/base.svelte';
 
 interface Row {
@@ -11,6 +12,8 @@ export type ConfigKey =
     | 'welcome-agreed'
     | 'skipped-version'
     | 'default-model'
+    | 'mcp-policy'
+    | 'mcp-metrics-port'
     | 'null';
 
 export default class Config extends Base<Row>('config') {
@@ -30,6 +33,13 @@ export default class Config extends Base<Row>('config') {
     @getset('default-model')
     static defaultModel: string;
 
+    @getset('mcp-policy')
+    static mcpPolicy: McpPolicyRule[];
+
+    // Port to serve MCP metrics for Prometheus on, if at all
+    @getset('mcp-metrics-port')
+    static mcpMetricsPort: number | undefined;
+
     protected static async fromSql(row: Row): Promise<Config> {
         return Config.new({
             id: row.id,
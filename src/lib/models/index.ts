@@ -8,6 +8,8 @@ import McpServer from '$lib/models/mcp-server.svelte';
 import Message from '$lib/models/message.svelte';
 import Model from '$lib/models/model.svelte';
 import Relay from '$lib/models/relay.svelte';
+import Root from '$lib/models/root.svelte';
+import SamplingRequest from '$lib/models/sampling-request.svelte';
 import Session from '$lib/models/session.svelte';
 import Setting from '$lib/models/setting.svelte';
 import Task from '$lib/models/task.svelte';
@@ -25,6 +27,8 @@ export { default as McpServer } from '$lib/models/mcp-server.svelte';
 export { default as Message } from '$lib/models/message.svelte';
 export { default as Model } from '$lib/models/model.svelte';
 export { default as Relay } from '$lib/models/relay.svelte';
+export { default as Root } from '$lib/models/root.svelte';
+export { default as SamplingRequest } from '$lib/models/sampling-request.svelte';
 export { default as Session } from '$lib/models/session.svelte';
 export { default as Setting } from '$lib/models/setting.svelte';
 export { default as Task } from '$lib/models/task.svelte';
@@ -41,6 +45,8 @@ export async function resync() {
     await Message.sync();
     await McpServer.sync();
     await Relay.sync();
+    await Root.sync();
+    await SamplingRequest.sync();
     await Setting.sync();
     await Config.sync();
     await Task.sync();
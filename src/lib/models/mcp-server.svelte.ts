@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
 
+import type { McpConfig, McpServerOptions, McpTool, McpTransport } from '$lib/mcp';
 import { Session, type ToSqlRow } from '$lib/models';
 import Base from '$lib/models/base.svelte';
 
@@ -10,6 +11,11 @@ interface Row {
     metadata: string;
     args: string;
     env: string;
+    transport: McpTransport;
+    url: string | null;
+    headers: string;
+    bearer_token: string | null;
+    options: string;
 }
 
 interface Metadata {
@@ -21,6 +27,8 @@ interface Metadata {
         name?: string;
         version: string;
     };
+    // Missing from servers added before tools were recorded
+    tools?: McpTool[];
 }
 
 export default class McpServer extends Base<Row>('mcp_servers') {
@@ -30,6 +38,11 @@ export default class McpServer extends Base<Row>('mcp_servers') {
     metadata?: Metadata = $state({} as Metadata);
     args: string[] = $state([]);
     env: Record<string, string> = $state({});
+    transport: McpTransport = $state('stdio');
+    url?: string = $state();
+    headers: Record<string, string> = $state({});
+    bearerToken?: string = $state();
+    options: McpServerOptions = $state({});
 
     get defaults() {
         return {
@@ -47,6 +60,9 @@ export default class McpServer extends Base<Row>('mcp_servers') {
             },
             args: [],
             env: {},
+            transport: 'stdio' as McpTransport,
+            headers: {},
+            options: {},
         };
     }
 
@@ -64,12 +80,32 @@ export default class McpServer extends Base<Row>('mcp_servers') {
         );
     }
 
-    async start(session: Session) {
-        await invoke('start_mcp_server', {
-            sessionId: session.id,
+    get config(): McpConfig {
+        return {
+            ...this.options,
+            transport: this.transport,
             command: this.command,
             args: this.args,
             env: this.env,
+            url: this.url,
+            headers: this.headers,
+            bearerToken: this.bearerToken,
+        };
+    }
+
+    // Ask the server about itself again, rather than going by what was cached
+    // last time, as after it's been upgraded.
+    //
+    async refreshMetadata() {
+        await invoke('invalidate_metadata', { config: this.config });
+        this.metadata = JSON.parse(await invoke('get_metadata', { config: this.config }));
+        await this.save();
+    }
+
+    async start(session: Session) {
+        await invoke('start_mcp_server', {
+            sessionId: session.id,
+            config: this.config,
         });
     }
 
@@ -80,12 +116,34 @@ export default class McpServer extends Base<Row>('mcp_servers') {
         });
     }
 
+    // Catch commands that can't run before they're saved, rather than when a
+    // session tries to start them.
+    //
+    async beforeSave(row: Row): Promise<ToSqlRow<Row>> {
+        if (row.transport == 'stdio') {
+            const options: McpServerOptions = JSON.parse(row.options);
+            await invoke('validate_mcp_command', {
+                command: options.container ? (options.container.runtime ?? 'docker') : row.command,
+                cwd: options.cwd,
+            });
+        }
+
+        return row;
+    }
+
     async beforeCreate(row: Row): Promise<ToSqlRow<Row>> {
         const metadata: Metadata = JSON.parse(
             await invoke('get_metadata', {
-                command: row.command,
-                args: JSON.parse(row.args),
-                env: JSON.parse(row.env),
+                config: {
+                    transport: row.transport,
+                    command: row.command,
+                    args: JSON.parse(row.args),
+                    env: JSON.parse(row.env),
+                    url: row.url ?? undefined,
+                    headers: JSON.parse(row.headers),
+                    bearerToken: row.bearer_token ?? undefined,
+                    ...JSON.parse(row.options),
+                },
             })
         );
 
@@ -105,6 +163,11 @@ export default class McpServer extends Base<Row>('mcp_servers') {
             metadata: JSON.parse(row.metadata),
             args: JSON.parse(row.args),
             env: JSON.parse(row.env),
+            transport: row.transport,
+            url: row.url ?? undefined,
+            headers: JSON.parse(row.headers),
+            bearerToken: row.bearer_token ?? undefined,
+            options: JSON.parse(row.options),
         });
     }
 
@@ -115,6 +178,11 @@ export default class McpServer extends Base<Row>('mcp_servers') {
             metadata: JSON.stringify(this.metadata),
             args: JSON.stringify(this.args),
             env: JSON.stringify(this.env),
+            transport: this.transport,
+            url: this.url ?? null,
+            headers: JSON.stringify(this.headers),
+            bearer_token: this.bearerToken ?? null,
+            options: JSON.stringify(this.options),
         };
     }
 
@@ -0,0 +1,52 @@
+import moment from 'moment';
+
+import Base, { type ToSqlRow } from '$lib/models/base.svelte';
+
+interface Row {
+    id: number;
+    app_id?: number;
+    session_id?: number;
+    path: string;
+    name?: string;
+    created: string;
+    modified: string;
+}
+
+/**
+ * A directory MCP servers may work in. Belongs to either an app, covering
+ * every session it runs, or to a single session.
+ */
+export default class Root extends Base<Row>('roots') {
+    id?: number = $state();
+    appId?: number = $state();
+    sessionId?: number = $state();
+    path: string = $state('');
+    name?: string = $state();
+    created?: moment.Moment = $state();
+    modified?: moment.Moment = $state();
+
+    get uri(): string {
+        return `file://${encodeURI(this.path)}`;
+    }
+
+    protected static async fromSql(row: Row): Promise<Root> {
+        return Root.new({
+            id: row.id,
+            appId: row.app_id,
+            sessionId: row.session_id,
+            path: row.path,
+            name: row.name,
+            created: moment.utc(row.created),
+            modified: moment.utc(row.modified),
+        });
+    }
+
+    protected async toSql(): Promise<ToSqlRow<Row>> {
+        return {
+            app_id: this.appId,
+            session_id: this.sessionId,
+            path: this.path,
+            name: this.name,
+        };
+    }
+}
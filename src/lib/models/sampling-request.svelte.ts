@@ -0,0 +1,92 @@
+import moment from 'moment';
+
+import type { McpSamplingParams } from '$lib/mcp';
+import { Session } from '$lib/models';
+import Base, { type ToSqlRow } from '$lib/models/base.svelte';
+
+export type SamplingStatus =
+    | 'pending'
+    | 'approved'
+    | 'denied'
+    | 'over_budget'
+    | 'failed'
+    | 'cancelled';
+
+interface Row {
+    id: number;
+    session_id: number;
+    server: string;
+    tool_call_id?: string;
+    engine_id?: number;
+    model?: string;
+    request: string;
+    response?: string;
+    status: string;
+    tokens: number;
+    created: string;
+    modified: string;
+}
+
+/**
+ * A completion an MCP server asked a session's engine for, whether or not it
+ * got one.
+ */
+export default class SamplingRequest extends Base<Row>('mcp_sampling_requests') {
+    id?: number = $state();
+    sessionId?: number = $state();
+    server: string = $state('');
+    toolCallId?: string = $state();
+    engineId?: number = $state();
+    model?: string = $state();
+    request: McpSamplingParams = $state({ messages: [], maxTokens: 0 });
+    response?: string = $state();
+    status: SamplingStatus = $state('approved');
+    // What the request was charged against the server's budget
+    tokens: number = $state(0);
+    created?: moment.Moment = $state();
+    modified?: moment.Moment = $state();
+
+    get session(): Session {
+        return Session.find(this.sessionId as number);
+    }
+
+    // Tokens a server has spent in a session so far, counting those reserved
+    // by requests still in flight.
+    //
+    static spent(sessionId: number, server: string): number {
+        return this.where({ sessionId, server })
+            .filter(request => request.status == 'approved' || request.status == 'pending')
+            .reduce((total, request) => total + request.tokens, 0);
+    }
+
+    protected static async fromSql(row: Row): Promise<SamplingRequest> {
+        return SamplingRequest.new({
+            id: row.id,
+            sessionId: row.session_id,
+            server: row.server,
+            toolCallId: row.tool_call_id,
+            engineId: row.engine_id,
+            model: row.model,
+            request: JSON.parse(row.request),
+            response: row.response,
+            status: row.status as SamplingStatus,
+            tokens: row.tokens,
+            created: moment.utc(row.created),
+            modified: moment.utc(row.modified),
+        });
+    }
+
+    protected async toSql(): Promise<ToSqlRow<Row>> {
+        return {
+            session_id: this.sessionId as number,
+            server: this.server,
+            tool_call_id: this.toolCallId,
+            engine_id: this.engineId,
+            model: this.model,
+            request: JSON.stringify(this.request),
+            response: this.response,
+            status: this.status,
+            tokens: this.tokens,
+        };
+    }
+}
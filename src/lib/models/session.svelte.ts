@@ -1,8 +1,8 @@
 import moment from 'moment';
 
 import type { Tool } from '$lib/engines/types';
-import { getMcpTools } from '$lib/mcp';
-import { App, McpServer, Message, Model, Setting } from '$lib/models';
+import { getMcpTools, setMcpRoots } from '$lib/mcp';
+import { App, McpServer, Message, Model, Root, Setting } from '$lib/models';
 import Base, { type ToSqlRow } from '$lib/models/base.svelte';
 import { DEFAULT_SUMMARY } from '$lib/summarize';
 
@@ -23,6 +23,9 @@ interface Config {
     contextWindow: number;
     temperature: number;
     enabledMcpServers: string[];
+    // Engine and model MCP servers sample with, unless they pick their own.
+    // Defaults to the session's.
+    sampling?: { engineId: number; model: string };
 }
 
 interface Row {
@@ -74,11 +77,20 @@ export default class Session extends Base<Row>('sessions') {
             .compact();
     }
 
+    // Directories MCP servers may work in: the session's own, and its app's.
+    get roots(): Root[] {
+        return [
+            ...Root.where({ sessionId: this.id }),
+            ...(this.appId ? Root.where({ appId: this.appId }) : []),
+        ];
+    }
+
     get model() {
         return Model.findBy({ engineId: this.config.engineId, id: this.config.model });
     }
 
     async start() {
+        await this.syncRoots();
         await this.mcpServers.awaitAll(async s => await s.start(this));
     }
 
@@ -90,6 +102,24 @@ export default class Session extends Base<Row>('sessions') {
         return this.id ? await getMcpTools(this.id) : [];
     }
 
+    async addRoot(path: string, name?: string): Promise<Root> {
+        const root = await Root.create({ sessionId: this.id, path, name });
+        await this.syncRoots();
+        return root;
+    }
+
+    async removeRoot(root: Root) {
+        await root.delete();
+        await this.syncRoots();
+    }
+
+    // Send the session's roots to its MCP servers.
+    async syncRoots() {
+        if (this.id) {
+            await setMcpRoots(this.id, this.roots);
+        }
+    }
+
     hasUserMessages(): boolean {
         return Message.exists({ sessionId: this.id, role: 'user' });
     }
@@ -0,0 +1,22 @@
+import { type McpPolicyRule, setMcpPolicy } from '$lib/mcp';
+import Config from '$lib/models/config.svelte';
+
+// The rules tool calls are checked against, as last saved.
+//
+export function rules(): McpPolicyRule[] {
+    return Config.mcpPolicy ?? [];
+}
+
+// Hand the saved rules over to the backend, which starts out allowing every
+// call.
+//
+export async function load() {
+    await setMcpPolicy(rules());
+}
+
+// Put `rules` in place and save them. Rules the backend rejects aren't saved.
+//
+export async function save(rules: McpPolicyRule[]) {
+    await setMcpPolicy(rules);
+    Config.mcpPolicy = rules;
+}
@@ -0,0 +1,172 @@
+import {
+    type McpSamplingEvent,
+    type McpSamplingResponse,
+    respondToMcpSampling,
+} from '$lib/mcp';
+import { error } from '$lib/logger';
+import { Engine, Message, Model, SamplingRequest, Session } from '$lib/models';
+import type { SamplingStatus } from '$lib/models/sampling-request.svelte';
+import { type Approval, approvals } from '$lib/stores/sampling';
+
+/**
+ * Decides whether a server may have the completion it's asking for. By
+ * default the user is asked, via the approval modal.
+ */
+export type Approver = (event: McpSamplingEvent) => Promise<Approval>;
+
+// The last budget check of each server in each session, so that the next
+// waits for it to reserve what it asked for.
+//
+const budgetChecks = new Map<string, Promise<unknown>>();
+
+function serially<T>(key: string, check: () => Promise<T>): Promise<T> {
+    const next = (budgetChecks.get(key) ?? Promise.resolve()).then(check, check);
+    budgetChecks.set(key, next.catch(() => {}));
+    return next;
+}
+
+/**
+ * Handle a server's `sampling/createMessage` request: check it against the
+ * server's budget, get it approved, run it past an engine, and record how it
+ * went in the session's history.
+ */
+export async function sample(event: McpSamplingEvent, approve: Approver = approvals.ask) {
+    const session = Session.find(event.sessionId);
+    const model = pick(event, session);
+    const { maxTokens } = event.request;
+
+    const fields = {
+        sessionId: event.sessionId,
+        server: event.server,
+        toolCallId: event.toolCallId,
+        engineId: model?.engineId,
+        model: model?.id,
+        request: event.request,
+    };
+
+    // The request's row, written as soon as it's within budget, so that its
+    // tokens are spoken for while it's waiting on the user or the engine.
+    let reserved: SamplingRequest | undefined;
+
+    // Only approved requests are charged; the rest give back what they
+    // reserved.
+    const record = async (status: SamplingStatus, response?: string) => {
+        const tokens = status == 'approved' ? maxTokens : 0;
+        if (reserved) {
+            await reserved.update({ status, response, tokens });
+        } else {
+            await SamplingRequest.create({ ...fields, response, status, tokens });
+        }
+    };
+
+    const reserve = () =>
+        serially(`${event.sessionId}:${event.server}`, async () => {
+            const { budget } = event.sampling;
+            const spent = SamplingRequest.spent(event.sessionId, event.server);
+            if (budget != null && spent + maxTokens > budget) {
+                return `Sampling budget exhausted (${spent} of ${budget} tokens spent)`;
+            }
+
+            reserved = await SamplingRequest.create({
+                ...fields,
+                status: 'pending',
+                tokens: maxTokens,
+            });
+        });
+
+    const reject = async (status: SamplingStatus, reason: string) => {
+        await record(status, reason);
+        return { error: reason };
+    };
+
+    const respond = async (): Promise<McpSamplingResponse | undefined> => {
+        if (!session) {
+            return { error: `MissingSessionError: ${event.sessionId}` };
+        }
+
+        const engine = model ? Engine.find(Number(model.engineId)) : undefined;
+        if (!model || !engine?.client) {
+            return await reject('failed', 'No engine available to sample with');
+        }
+
+        const exhausted = await reserve();
+        if (exhausted) {
+            return await reject('over_budget', exhausted);
+        }
+
+        const approval = event.sampling.autoApprove ? 'approved' : await approve(event);
+        if (approval == 'cancelled') {
+            await record('cancelled');
+            return;
+        }
+        if (approval == 'denied') {
+            return await reject('denied', 'Sampling request denied by the user');
+        }
+
+        const history = [];
+        if (event.request.systemPrompt) {
+            history.push(Message.new({ role: 'system', content: event.request.systemPrompt }));
+        }
+        for (const message of event.request.messages) {
+            if (message.content.type != 'text') {
+                const type = message.content.type;
+                return await reject('failed', `Unsupported content type: ${type}`);
+            }
+            history.push(Message.new({ role: message.role, content: message.content.text }));
+        }
+
+        try {
+            const reply = await engine.client.chat(model, history, [], {
+                temperature: event.request.temperature,
+                num_predict: maxTokens,
+                stop: event.request.stopSequences,
+            });
+
+            await record('approved', reply.content);
+
+            return {
+                result: {
+                    model: model.name,
+                    stopReason: 'endTurn',
+                    role: 'assistant',
+                    content: { type: 'text', text: reply.content },
+                },
+            };
+        } catch (e) {
+            error('SamplingError', e);
+            return await reject('failed', String(e));
+        }
+    };
+
+    const response = await respond();
+
+    // Cancelled requests have nobody left to answer.
+    if (response) {
+        await respondToMcpSampling(event.id, response);
+    }
+}
+
+// The model a server samples with: its own pick, or the session's sampling
+// model, or the first model matching the server's hints, or the session's
+// model.
+//
+function pick(event: McpSamplingEvent, session?: Session): Model | undefined {
+    const { engineId, model } = event.sampling;
+    if (engineId != null && model) {
+        return Model.findBy({ engineId, id: model });
+    }
+
+    const sampling = session?.config.sampling;
+    if (sampling) {
+        return Model.findBy({ engineId: sampling.engineId, id: sampling.model });
+    }
+
+    for (const { name } of event.request.modelPreferences?.hints ?? []) {
+        const hinted = name ? Model.all().find(model => model.name.includes(name)) : undefined;
+        if (hinted) {
+            return hinted;
+        }
+    }
+
+    return session?.model;
+}
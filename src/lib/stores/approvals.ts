@@ -0,0 +1,42 @@
+import { writable } from 'svelte/store';
+
+export type Approval = 'approved' | 'denied' | 'cancelled';
+
+export interface PendingApproval<T> {
+    request: T;
+    resolve: (approval: Approval) => void;
+}
+
+// A queue of requests waiting on the user, settled one at a time by whichever
+// modal is showing them.
+//
+export const createApprovalStore = <T extends { id: string }>() => {
+    const { subscribe, update } = writable<PendingApproval<T>[]>([]);
+
+    const settle = (id: string, approval: Approval) => {
+        update(pending =>
+            pending.filter(p => {
+                if (p.request.id == id) {
+                    p.resolve(approval);
+                    return false;
+                }
+                return true;
+            })
+        );
+    };
+
+    return {
+        subscribe,
+
+        // Queue a request for the user to approve, resolving once they have
+        // (or haven't).
+        ask: (request: T) =>
+            new Promise<Approval>(resolve => {
+                update(pending => [...pending, { request, resolve }]);
+            }),
+
+        approve: (id: string) => settle(id, 'approved'),
+        deny: (id: string) => settle(id, 'denied'),
+        cancel: (id: string) => settle(id, 'cancelled'),
+    };
+};
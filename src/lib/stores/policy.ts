@@ -0,0 +1,4 @@
+import type { McpToolApprovalEvent } from '$lib/mcp';
+import { createApprovalStore } from '$lib/stores/approvals';
+
+export const toolApprovals = createApprovalStore<McpToolApprovalEvent>();
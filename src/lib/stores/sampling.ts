@@ -0,0 +1,6 @@
+import type { McpSamplingEvent } from '$lib/mcp';
+import { createApprovalStore } from '$lib/stores/approvals';
+
+export type { Approval } from '$lib/stores/approvals';
+
+export const approvals = createApprovalStore<McpSamplingEvent>();